@@ -0,0 +1,169 @@
+//! Compares the per-pair Dijkstra that `Network::calculate_routes` used to run with the
+//! per-source shortest-path trees it runs now. Run with
+//! `cargo bench --no-default-features --bench routes`.
+
+use indexmap::IndexMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use visualize_network::network::ether::LinkAttributes;
+use visualize_network::network::node::NodeId;
+use visualize_network::network::routing::Cost;
+use visualize_network::network::{Network, Point};
+
+/// A `side` x `side` grid of routers with slightly jittered positions, so that edge costs
+/// (screen distances) differ and ties are rare.
+fn grid(side: i32) -> Network {
+    let mut rng = StdRng::seed_from_u64(side as u64);
+    let mut network = Network::new();
+    let mut nodes = HashMap::new();
+    for row in 0..side {
+        for column in 0..side {
+            let position = Point::new(
+                column * 40 + rng.gen_range(0..20),
+                row * 40 + rng.gen_range(0..20),
+            );
+            nodes.insert((row, column), network.add_router_node(position));
+        }
+    }
+    for row in 0..side {
+        for column in 0..side {
+            for (next_row, next_column) in [(row + 1, column), (row, column + 1)] {
+                if let Some(next) = nodes.get(&(next_row, next_column)) {
+                    let node = nodes[&(row, column)];
+                    let i1 = network
+                        .add_router_interface(node, format!("{}-{}", node, next))
+                        .unwrap();
+                    let i2 = network
+                        .add_router_interface(*next, format!("{}-{}", next, node))
+                        .unwrap();
+                    network
+                        .connect_cable(((node, i1), (*next, i2)), LinkAttributes::default())
+                        .unwrap();
+                }
+            }
+        }
+    }
+    network
+}
+
+/// The algorithm `calculate_routes` ran for every source/destination pair before.
+fn naive_preferred_path(
+    source: NodeId,
+    destination: NodeId,
+    distances: &[(NodeId, NodeId, Cost)],
+) -> Option<Vec<NodeId>> {
+    let mut visited = HashMap::new();
+    let mut shortest: IndexMap<NodeId, (NodeId, Cost)> = IndexMap::new();
+    shortest.insert(source, (source, 0));
+
+    while let Some((&current_id, _)) = shortest.first() {
+        let (previous_id, current_distance) = shortest.shift_remove(&current_id).unwrap();
+        if current_id != destination {
+            for (from, to, distance) in distances.iter() {
+                if *from == current_id {
+                    let distance_to_next = current_distance + distance;
+                    if !visited.contains_key(to)
+                        && shortest
+                            .get(to)
+                            .is_none_or(|(_, known)| distance_to_next < *known)
+                    {
+                        shortest.insert(*to, (current_id, distance_to_next));
+                    }
+                }
+            }
+            shortest.sort_by(|_, a, _, b| a.1.cmp(&b.1));
+        }
+        visited.insert(current_id, previous_id);
+    }
+
+    let mut path = vec![destination];
+    let mut current = destination;
+    while current != source {
+        current = *visited.get(&current)?;
+        path.push(current);
+    }
+    path.reverse();
+    Some(path)
+}
+
+fn path_cost(path: &[NodeId], costs: &HashMap<(NodeId, NodeId), Cost>) -> Cost {
+    path.windows(2).map(|hop| costs[&(hop[0], hop[1])]).sum()
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn main() {
+    println!(
+        "{:>6} {:>12} {:>12} {:>8}",
+        "nodes", "per-pair", "per-source", "speedup"
+    );
+    for side in [5, 8, 10] {
+        let graph = grid(side).routing_graph().unwrap();
+        let mut nodes: Vec<NodeId> = graph.nodes().collect();
+        nodes.sort();
+        let mut edges = vec![];
+        let mut costs = HashMap::new();
+        for from in nodes.iter() {
+            for (to, cost) in graph.neighbors(*from) {
+                edges.push((*from, *to, *cost));
+                costs.insert((*from, *to), *cost);
+            }
+        }
+
+        let (naive, naive_time) = time(|| {
+            let mut paths = vec![];
+            for source in nodes.iter() {
+                for destination in nodes.iter() {
+                    paths.push(naive_preferred_path(*source, *destination, &edges).unwrap());
+                }
+            }
+            paths
+        });
+        let (trees, trees_time) = time(|| {
+            nodes
+                .iter()
+                .map(|source| graph.shortest_path_tree(*source))
+                .collect::<Vec<_>>()
+        });
+
+        for (tree, paths) in trees.iter().zip(naive.chunks(nodes.len())) {
+            for (destination, path) in nodes.iter().zip(paths) {
+                assert_eq!(tree.distance(*destination), Some(path_cost(path, &costs)));
+            }
+        }
+
+        println!(
+            "{:>6} {:>10.1}ms {:>10.1}ms {:>7.0}x",
+            nodes.len(),
+            naive_time.as_secs_f64() * 1000.0,
+            trees_time.as_secs_f64() * 1000.0,
+            naive_time.as_secs_f64() / trees_time.as_secs_f64()
+        );
+    }
+
+    println!();
+    println!("{:>6} {:>12} {:>12}", "nodes", "trees", "routes");
+    for side in [20, 35, 50] {
+        let mut network = grid(side);
+        let graph = network.routing_graph().unwrap();
+        let (_, trees_time) = time(|| {
+            for source in graph.nodes() {
+                graph.shortest_path_tree(source);
+            }
+        });
+        let (result, routes_time) = time(|| network.calculate_routes());
+        result.unwrap();
+        println!(
+            "{:>6} {:>10.1}ms {:>10.1}ms",
+            side * side,
+            trees_time.as_secs_f64() * 1000.0,
+            routes_time.as_secs_f64() * 1000.0
+        );
+    }
+}
@@ -1,40 +1,64 @@
-pub mod scenario;
-
-use super::network::node::NodeId;
-use crate::network::Network;
-use scenario::Scenario;
+pub mod view;
+
+use crate::network::error::NetworkError;
+use crate::network::node::NodeId;
+use crate::network::packet::{DEFAULT_TTL, PACKET_SIZE};
+use crate::network::recording::{Recording, Replay};
+use crate::network::routing::Routing;
+use crate::network::{
+    self, Network, SimTime, BACK, DELETE, DIJKSTRA, LATENCY_COST, ROUND_ROBIN, TIME_EXCEEDED,
+};
+use crate::scenario::Scenario;
 use sdl2::event::Event;
 use sdl2::image::LoadTexture;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
 use sdl2::rect::{Point, Rect};
 use sdl2::render::{Texture, TextureCreator, TextureQuery, WindowCanvas};
 use sdl2::ttf::{Font, Sdl2TtfContext};
 use sdl2::video::WindowContext;
-use sdl2::{EventPump, TimerSubsystem};
-use std::collections::{HashSet, VecDeque};
+use sdl2::EventPump;
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
+use view::{Glyphs, NetworkView};
 
 pub static SOURCE_NODE: AtomicUsize = AtomicUsize::new(0);
 pub static DESTINATION_NODE: AtomicUsize = AtomicUsize::new(16);
-pub static DIJKSTRA: AtomicBool = AtomicBool::new(true);
-pub static BACK: AtomicBool = AtomicBool::new(false);
 pub static SLOW: AtomicBool = AtomicBool::new(false);
-pub static DELETE: AtomicBool = AtomicBool::new(false);
+pub static ROUTES: AtomicBool = AtomicBool::new(false);
+pub static STATS: AtomicBool = AtomicBool::new(false);
+pub static HEATMAP: AtomicBool = AtomicBool::new(false);
+
+/// Upper bound on simulated time advanced per frame at normal speed, so a stalled window
+/// doesn't fast-forward.
+const MAX_FRAME_TIME: SimTime = 100;
+
+/// Fastest (and, inverted, slowest) replay speed.
+const MAX_REPLAY_SPEED: f64 = 64.0;
 
 pub struct App {
     pub network: Network,
     nodes: Vec<NodeId>,
+    time_scale: f64,
+    seed: Option<u64>,
+    routing: Option<Routing>,
 }
 
 impl App {
-    pub fn new() -> Result<App, String> {
+    pub fn new(
+        time_scale: f64,
+        seed: Option<u64>,
+        routing: Option<Routing>,
+    ) -> Result<App, String> {
         Ok(App {
             network: Network::new(),
             nodes: vec![],
+            time_scale,
+            seed,
+            routing,
         })
     }
 
@@ -43,91 +67,22 @@ impl App {
 
         let mut renderer = Renderer::new(&ttf_context)?;
 
-        let (use_scenario, nodes_positions, cable_connections, mut events) = match scenario {
-            Some(scenario) => (
-                true,
-                scenario.nodes,
-                scenario.cable_connections,
-                scenario.transmissions,
-            ),
-            None => (
-                false,
-                Vec::from([
-                    (80, 80, true),
-                    (150, 130, false),
-                    (290, 50, false),
-                    (80, 300, true),
-                    (120, 510, true),
-                    (200, 360, false),
-                    (400, 520, true),
-                    (320, 280, false),
-                    (480, 120, false),
-                    (650, 150, true),
-                    (300, 370, false),
-                    (560, 40, true),
-                    (520, 320, false),
-                    (630, 360, false),
-                    (640, 440, false),
-                    (570, 560, true),
-                    (760, 490, true),
-                ]),
-                Vec::from([
-                    (0, 1),
-                    (1, 3),
-                    (3, 5),
-                    (4, 5),
-                    (5, 10),
-                    (10, 7),
-                    (1, 7),
-                    (1, 2),
-                    (2, 8),
-                    (8, 11),
-                    (11, 9),
-                    (6, 10),
-                    (10, 12),
-                    (7, 12),
-                    (8, 12),
-                    (12, 13),
-                    (13, 14),
-                    (14, 15),
-                    (14, 16),
-                    (9, 12),
-                ]),
-                VecDeque::new(),
-            ),
-        };
-
-        let nodes: Vec<NodeId> = nodes_positions
-            .iter()
-            .map(|(x, y, is_endpoint)| {
-                if *is_endpoint {
-                    self.network
-                        .add_endpoint_node(&mut renderer, Point::new(*x, *y))
-                } else {
-                    self.network
-                        .add_router_node(&mut renderer, Point::new(*x, *y))
-                }
-            })
-            .collect();
-
-        cable_connections.iter().for_each(|(ind1, ind2)| {
-            let i1 = self
-                .network
-                .add_router_interface(nodes[*ind1], format!("{}-{}", ind1, ind2).to_string())
-                .unwrap();
-            let i2 = self
-                .network
-                .add_router_interface(nodes[*ind2], format!("{}-{}", ind2, ind1).to_string())
-                .unwrap();
-            self.network
-                .connect_cable(((nodes[*ind1], i1), (nodes[*ind2], i2)));
-        });
-
+        let use_scenario = scenario.is_some();
+        let mut scenario = scenario.unwrap_or_default();
+        if self.seed.is_some() {
+            scenario.seed = self.seed;
+        }
+        if let Some(routing) = self.routing.take() {
+            scenario.routing = routing;
+        }
+        let nodes = scenario.build(&mut self.network)?;
         self.nodes.extend(nodes);
+        eprintln!("Seed: {}", self.network.get_seed());
 
-        self.network.calculate_routes();
+        let view = NetworkView::new(&renderer, self.network.nodes().map(|node| node.get_id()))?;
 
         let mut prev_mouse_buttons = HashSet::new();
+        let mut selected_packet: Option<Uuid> = None;
 
         let mut _flag_texture_x = 40;
         let flags_textures = [
@@ -135,6 +90,12 @@ impl App {
             ("BACK", &BACK),
             ("SLOW", &SLOW),
             ("DELETE", &DELETE),
+            ("ROUTES", &ROUTES),
+            ("STATS", &STATS),
+            ("HEATMAP", &HEATMAP),
+            ("ROUND-ROBIN", &ROUND_ROBIN),
+            ("LATENCY", &LATENCY_COST),
+            ("TIME-EXCEEDED", &TIME_EXCEEDED),
         ]
         .map(|(str, flag)| {
             let (texture, mut rect) = renderer
@@ -164,71 +125,76 @@ impl App {
             (packets_count_texture.1.height() / 2) as i32,
         );
 
-        let ascii_textures: Vec<Option<(Texture, Rect)>> = (0..=255)
-            .map(|code: u8| {
-                let c = code as char;
-                if !c.is_ascii_control() {
-                    renderer
-                        .make_text(&format!("{}", code as char), Point::new(0, 0), Color::BLACK)
-                        .ok()
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let glyphs = Glyphs::new(&renderer);
 
-        let mut next_event_time = 0;
+        let mut last_frame = Instant::now();
+        let mut pending_time = 0f64;
+        // Flow statistics along with the packet updates they were made at.
+        let mut flows = (u64::MAX, vec![]);
 
         'main: loop {
-            if use_scenario {
-                let time = renderer.timer_subsystem.ticks();
-                if time >= next_event_time {
-                    'process_events: loop {
-                        if events.is_empty() {
-                            break 'process_events;
-                        }
-                        let event = events.front().unwrap();
-                        if event.0 > time {
-                            next_event_time = event.0;
-                            break 'process_events;
-                        }
-                        self.network.send(event.1, event.2, event.3);
-                        events.pop_front();
-                    }
-                }
-            } else {
-                for event in renderer.event_pump.poll_iter() {
-                    match event {
-                        Event::Quit { .. }
-                        | Event::KeyDown {
-                            keycode: Some(Keycode::Escape),
-                            ..
-                        } => break 'main,
-                        Event::KeyDown {
-                            keycode: Some(keycode),
-                            ..
-                        } => match keycode {
-                            Keycode::Space => self.network.send(
-                                Uuid::new_v4(),
+            for event in renderer.event_pump.poll_iter() {
+                match event {
+                    Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => break 'main,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => match keycode {
+                        // Scenarios schedule their own packets.
+                        Keycode::Space if !use_scenario => {
+                            let uuid = self.network.generate_uuid();
+                            match self.network.send(
+                                uuid,
                                 SOURCE_NODE.load(Ordering::Relaxed),
                                 DESTINATION_NODE.load(Ordering::Relaxed),
-                            ),
-                            Keycode::D => {
-                                DIJKSTRA.fetch_xor(true, Ordering::Relaxed);
-                            }
-                            Keycode::Backspace => {
-                                BACK.fetch_xor(true, Ordering::Relaxed);
-                            }
-                            Keycode::Minus => {
-                                SLOW.fetch_xor(true, Ordering::Relaxed);
+                                PACKET_SIZE,
+                                DEFAULT_TTL,
+                            ) {
+                                // The source or the destination was removed.
+                                Err(NetworkError::NodeNotFound(_)) => {}
+                                result => result?,
                             }
-                            Keycode::Delete => {
-                                DELETE.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::D => {
+                            DIJKSTRA.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::Backspace => {
+                            BACK.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::Minus => {
+                            SLOW.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::Delete => {
+                            DELETE.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::R => {
+                            ROUTES.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::S => {
+                            STATS.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::H => {
+                            HEATMAP.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::E => {
+                            ROUND_ROBIN.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::T => {
+                            TIME_EXCEEDED.fetch_xor(true, Ordering::Relaxed);
+                        }
+                        Keycode::L => {
+                            LATENCY_COST.fetch_xor(true, Ordering::Relaxed);
+                            if let Routing::Global = self.network.get_routing() {
+                                self.network.calculate_routes()?;
                             }
-                            _ => {}
-                        },
+                        }
                         _ => {}
-                    }
+                    },
+                    _ => {}
                 }
             }
 
@@ -240,21 +206,40 @@ impl App {
 
             if !new_mouse_buttons.is_empty() || !old_mouse_buttons.is_empty() {
                 if new_mouse_buttons.contains(&MouseButton::Left) {
-                    if let Some(node) = self
-                        .network
-                        .locate_node(Point::new(mouse_state.x(), mouse_state.y()))
-                    {
+                    let point = network::Point::new(mouse_state.x(), mouse_state.y());
+                    if let Some(uuid) = self.network.locate_packet(point) {
+                        selected_packet = Some(uuid);
+                    } else if let Some(node) = self.network.locate_node(point) {
+                        selected_packet = None;
                         SOURCE_NODE.store(node, Ordering::Relaxed);
                         source_destination_ids_texture = renderer.make_text(
                             &format!("{}-{}", node, DESTINATION_NODE.load(Ordering::Relaxed)),
                             source_destination_ids_texture.1.center(),
                             Color::RED,
                         )?;
+                    } else {
+                        selected_packet = None;
+                    }
+                } else if new_mouse_buttons.contains(&MouseButton::Middle) {
+                    let point = network::Point::new(mouse_state.x(), mouse_state.y());
+                    if let Some(node) = self.network.locate_node(point) {
+                        let keyboard_state = renderer.event_pump.keyboard_state();
+                        if keyboard_state.is_scancode_pressed(Scancode::LCtrl)
+                            || keyboard_state.is_scancode_pressed(Scancode::RCtrl)
+                        {
+                            self.network.remove_node(node)?;
+                        } else {
+                            let up = self.network.is_node_up(node)?;
+                            self.network.set_node_up(node, !up)?;
+                        }
+                    } else if let Some(ether) = self.network.locate_ether(point) {
+                        let up = self.network.is_link_up(ether)?;
+                        self.network.set_link_up(ether, !up)?;
                     }
                 } else if new_mouse_buttons.contains(&MouseButton::Right) {
                     if let Some(node) = self
                         .network
-                        .locate_node(Point::new(mouse_state.x(), mouse_state.y()))
+                        .locate_node(network::Point::new(mouse_state.x(), mouse_state.y()))
                     {
                         DESTINATION_NODE.store(node, Ordering::Relaxed);
                         source_destination_ids_texture = renderer.make_text(
@@ -270,7 +255,28 @@ impl App {
 
             renderer.canvas.set_draw_color(Color::WHITE);
             renderer.canvas.clear();
-            self.network.render(&mut renderer)?;
+
+            let now = Instant::now();
+            let time_scale = if SLOW.load(Ordering::Relaxed) {
+                self.time_scale / 10.0
+            } else {
+                self.time_scale
+            };
+            pending_time += (now - last_frame).as_secs_f64() * 1000.0 * time_scale;
+            pending_time = pending_time.min(MAX_FRAME_TIME as f64 * time_scale);
+            last_frame = now;
+            let elapsed = pending_time as SimTime;
+            pending_time -= elapsed as f64;
+            self.network.advance(elapsed)?;
+
+            if let Some(uuid) = selected_packet {
+                view.draw_path(&mut renderer, &self.network, uuid)?;
+            }
+            let heatmap = HEATMAP.load(Ordering::Relaxed);
+            view.draw(&mut renderer, &glyphs, &self.network, heatmap)?;
+            if heatmap {
+                view.draw_heatmap_legend(&mut renderer, &glyphs)?;
+            }
 
             renderer.canvas.copy(
                 &source_destination_ids_texture.0,
@@ -290,50 +296,134 @@ impl App {
                 Some(packets_count_texture.1),
             )?;
 
-            let mut _cont_digit_texture_x = packets_count_texture.1.right() + 5;
             let mut packets_count_string = format!("{}", self.network.get_packets_count());
             if use_scenario {
-                packets_count_string += &format!(" ({})", events.len());
+                packets_count_string += &format!(" ({})", self.network.get_scheduled_count());
+            }
+            glyphs.draw(
+                &mut renderer,
+                &packets_count_string,
+                packets_count_texture.1.right() + 5,
+                packets_count_texture.1.top(),
+            )?;
+
+            if ROUTES.load(Ordering::Relaxed) {
+                view.draw_routes(
+                    &mut renderer,
+                    &glyphs,
+                    &self.network,
+                    SOURCE_NODE.load(Ordering::Relaxed),
+                )?;
             }
-            for c in packets_count_string.chars() {
-                if let Some((texture, texture_rect)) = &ascii_textures[c as usize] {
-                    renderer.canvas.copy(
-                        texture,
-                        None,
-                        Rect::new(
-                            _cont_digit_texture_x,
-                            packets_count_texture.1.top(),
-                            texture_rect.width(),
-                            texture_rect.height(),
-                        ),
-                    )?;
-                    _cont_digit_texture_x += texture_rect.width() as i32;
+
+            if STATS.load(Ordering::Relaxed) {
+                let updates = self.network.get_packet_updates();
+                if flows.0 != updates {
+                    flows = (updates, self.network.flow_statistics());
                 }
+                view.draw_flows(&mut renderer, &glyphs, &flows.1)?;
             }
 
             renderer.canvas.present();
 
-            ::std::thread::sleep(Duration::new(
-                0,
-                if SLOW.load(Ordering::Relaxed) {
-                    1_000_000_000u32 / 60
-                } else {
-                    1_000_000_000u32 / 60 / 10
-                },
-            ));
+            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
 
-            if use_scenario && events.is_empty() && self.network.get_packets_count() == 0 {
+            if use_scenario && self.network.is_idle() {
                 break 'main;
             }
         }
 
         Ok(())
     }
+
+    /// Plays a recording back: `Space` pauses, `Up`/`Down` double or halve the speed,
+    /// `Left`/`Right` seek by a second (ten with `Shift`), `Home`/`End` jump to either end.
+    pub fn replay(&mut self, recording: Recording) -> Result<(), String> {
+        let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+
+        let mut renderer = Renderer::new(&ttf_context)?;
+        let glyphs = Glyphs::new(&renderer);
+
+        let mut replay = Replay::new(recording);
+        eprintln!("Seed: {}", replay.get_seed());
+        let view = NetworkView::new(&renderer, replay.nodes().map(|node| node.id))?;
+
+        let mut speed = self.time_scale;
+        let mut paused = false;
+        let mut last_frame = Instant::now();
+        let mut pending_time = 0f64;
+
+        'main: loop {
+            for event in renderer.event_pump.poll_iter() {
+                match event {
+                    Event::Quit { .. }
+                    | Event::KeyDown {
+                        keycode: Some(Keycode::Escape),
+                        ..
+                    } => break 'main,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        keymod,
+                        ..
+                    } => {
+                        let step: SimTime = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            10_000
+                        } else {
+                            1000
+                        };
+                        match keycode {
+                            Keycode::Space => paused = !paused,
+                            Keycode::Up => speed = (speed * 2.0).min(MAX_REPLAY_SPEED),
+                            Keycode::Down => speed = (speed / 2.0).max(1.0 / MAX_REPLAY_SPEED),
+                            Keycode::Right => replay.seek(replay.get_time() + step),
+                            Keycode::Left => replay.seek(replay.get_time().saturating_sub(step)),
+                            Keycode::Home => replay.seek(replay.get_start()),
+                            Keycode::End => replay.seek(replay.get_end()),
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            let now = Instant::now();
+            if !paused {
+                pending_time += (now - last_frame).as_secs_f64() * 1000.0 * speed;
+                pending_time = pending_time.min(MAX_FRAME_TIME as f64 * speed);
+                let elapsed = pending_time as SimTime;
+                pending_time -= elapsed as f64;
+                replay.advance(elapsed);
+            }
+            last_frame = now;
+
+            renderer.canvas.set_draw_color(Color::WHITE);
+            renderer.canvas.clear();
+            view.draw_replay(&mut renderer, &glyphs, &replay)?;
+
+            let mut status = format!(
+                "REPLAY {} / {} ms  x{}",
+                replay.get_time(),
+                replay.get_end(),
+                speed
+            );
+            if paused {
+                status += "  PAUSED";
+            } else if replay.is_finished() {
+                status += "  END";
+            }
+            glyphs.draw(&mut renderer, &status, 10, 5)?;
+
+            renderer.canvas.present();
+
+            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        }
+
+        Ok(())
+    }
 }
 
 pub struct Renderer<'r> {
     event_pump: EventPump,
-    pub(super) timer_subsystem: TimerSubsystem,
     pub canvas: WindowCanvas,
     pub font: Font<'r, 'r>,
     pub texture_creator: TextureCreator<WindowContext>,
@@ -367,13 +457,11 @@ impl<'r> Renderer<'r> {
         let packet_texture = texture_creator.load_texture("packet.png")?;
 
         let event_pump = sdl_context.event_pump()?;
-        let timer_subsystem = sdl_context.timer()?;
 
         let font = ttf_context.load_font("sample.ttf", 15)?;
 
         Ok(Self {
             event_pump,
-            timer_subsystem,
             canvas,
             font,
             texture_creator,
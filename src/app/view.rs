@@ -0,0 +1,459 @@
+use super::Renderer;
+use crate::network::ether::distance_between;
+use crate::network::node::{AsNumber, NodeId, NodeKind};
+use crate::network::packet::PacketKind;
+use crate::network::recording::Replay;
+use crate::network::report::FlowStatistics;
+use crate::network::{self, Impairment, Network};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Texture, TextureQuery};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+impl From<network::Point> for Point {
+    fn from(point: network::Point) -> Point {
+        Point::new(point.x(), point.y())
+    }
+}
+
+/// Pre-rendered ASCII characters, for text that changes every frame.
+pub struct Glyphs {
+    textures: Vec<Option<(Texture, Rect)>>,
+}
+
+impl Glyphs {
+    pub fn new(renderer: &Renderer) -> Glyphs {
+        let textures = (0..=255)
+            .map(|code: u8| {
+                let c = code as char;
+                if !c.is_ascii_control() {
+                    renderer
+                        .make_text(&format!("{}", c), Point::new(0, 0), Color::BLACK)
+                        .ok()
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Glyphs { textures }
+    }
+
+    /// Draws `text` with its top left corner at `(x, y)` and returns the x after its end.
+    pub fn draw(&self, renderer: &mut Renderer, text: &str, x: i32, y: i32) -> Result<i32, String> {
+        let mut x = x;
+        for c in text.chars() {
+            if let Some(Some((texture, rect))) = self.textures.get(c as usize) {
+                renderer.canvas.copy(
+                    texture,
+                    None,
+                    Rect::new(x, y, rect.width(), rect.height()),
+                )?;
+                x += rect.width() as i32;
+            }
+        }
+        Ok(x)
+    }
+}
+
+/// From blue for idle cables through cyan, green and yellow to red for saturated ones.
+fn heat_color(heat: f64) -> Color {
+    const STOPS: [(u8, u8, u8); 5] = [
+        (0, 0, 255),
+        (0, 200, 255),
+        (0, 200, 0),
+        (255, 210, 0),
+        (255, 0, 0),
+    ];
+    let position = heat.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let index = (position as usize).min(STOPS.len() - 2);
+    let fraction = position - index as f64;
+    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * fraction) as u8;
+    let ((r0, g0, b0), (r1, g1, b1)) = (STOPS[index], STOPS[index + 1]);
+    Color::RGB(mix(r0, r1), mix(g0, g1), mix(b0, b1))
+}
+
+/// Tints telling autonomous systems apart.
+const AS_COLORS: [(u8, u8, u8); 6] = [
+    (255, 170, 170),
+    (170, 255, 170),
+    (170, 170, 255),
+    (255, 255, 150),
+    (255, 170, 255),
+    (150, 255, 255),
+];
+
+pub struct NetworkView {
+    labels: HashMap<NodeId, Texture>,
+}
+
+impl NetworkView {
+    pub fn new(
+        renderer: &Renderer,
+        nodes: impl IntoIterator<Item = NodeId>,
+    ) -> Result<NetworkView, String> {
+        let mut labels = HashMap::new();
+        for node in nodes {
+            let (texture, _) =
+                renderer.make_text(&node.to_string(), Point::new(0, 0), Color::RED)?;
+            labels.insert(node, texture);
+        }
+        Ok(NetworkView { labels })
+    }
+
+    /// `heat` is how busy the cable is, from 0 to 1, when the heatmap is on.
+    fn draw_cable(
+        renderer: &mut Renderer,
+        (from, to): (network::Point, network::Point),
+        up: bool,
+        heat: Option<f64>,
+    ) -> Result<(), String> {
+        match (up, heat) {
+            (true, None) => {
+                renderer.canvas.set_draw_color(Color::BLACK);
+                renderer.canvas.draw_line(from, to)?;
+            }
+            (true, Some(heat)) => {
+                renderer.canvas.set_draw_color(heat_color(heat));
+                for offset in -1..=1 {
+                    renderer.canvas.draw_line(
+                        Point::from(from).offset(offset, 0),
+                        Point::from(to).offset(offset, 0),
+                    )?;
+                    renderer.canvas.draw_line(
+                        Point::from(from).offset(0, offset),
+                        Point::from(to).offset(0, offset),
+                    )?;
+                }
+            }
+            (false, _) => {
+                // A broken cable, with a gap in the middle: red, or gray not to look hot.
+                let (dx, dy) = (to.x() - from.x(), to.y() - from.y());
+                let gap_start = network::Point::new(from.x() + dx * 2 / 5, from.y() + dy * 2 / 5);
+                let gap_end = network::Point::new(from.x() + dx * 3 / 5, from.y() + dy * 3 / 5);
+                renderer.canvas.set_draw_color(match heat {
+                    Some(_) => Color::GRAY,
+                    None => Color::RED,
+                });
+                renderer.canvas.draw_line(from, gap_start)?;
+                renderer.canvas.draw_line(gap_end, to)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_load(
+        renderer: &mut Renderer,
+        glyphs: &Glyphs,
+        segments: &[(network::Point, network::Point)],
+        load: u64,
+    ) -> Result<(), String> {
+        if let (Some((from, to)), true) = (segments.first(), load > 0) {
+            let middle = network::Point::new((from.x() + to.x()) / 2, (from.y() + to.y()) / 2);
+            glyphs.draw(renderer, &load.to_string(), middle.x() + 4, middle.y() + 2)?;
+        }
+        Ok(())
+    }
+
+    fn draw_node(
+        &self,
+        renderer: &mut Renderer,
+        id: NodeId,
+        kind: NodeKind,
+        position: network::Point,
+        autonomous_system: Option<AsNumber>,
+        up: bool,
+    ) -> Result<(), String> {
+        let position: Point = position.into();
+        let texture = match kind {
+            NodeKind::Router => &mut renderer.node_texture,
+            NodeKind::Endpoint => &mut renderer.endpoint_texture,
+        };
+        let (r, g, b) = match autonomous_system {
+            Some(autonomous_system) => AS_COLORS[autonomous_system as usize % AS_COLORS.len()],
+            None => (255, 255, 255),
+        };
+        let (r, g, b) = if up { (r, g, b) } else { (r / 3, g / 3, b / 3) };
+        texture.set_color_mod(r, g, b);
+        renderer
+            .canvas
+            .copy(texture, None, Some(Rect::from_center(position, 50, 50)))?;
+        texture.set_color_mod(255, 255, 255);
+        if let Some(label) = self.labels.get(&id) {
+            let TextureQuery { width, height, .. } = label.query();
+            renderer.canvas.copy(
+                label,
+                None,
+                Some(Rect::from_center(position.offset(0, -10), width, height)),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_packet(
+        renderer: &mut Renderer,
+        glyphs: &Glyphs,
+        position: network::Point,
+        kind: PacketKind,
+        impairment: Option<Impairment>,
+    ) -> Result<(), String> {
+        let (r, g, b) = match (impairment, kind) {
+            (Some(Impairment::Lost), _) => (40, 40, 40),
+            (Some(Impairment::Corrupted), _) => (255, 0, 0),
+            (_, PacketKind::Data) => (255, 255, 255),
+            (_, PacketKind::DistanceVector) => (80, 80, 255),
+            (_, PacketKind::Hello) => (160, 160, 160),
+            (_, PacketKind::LinkState) => (255, 150, 0),
+            (_, PacketKind::PathVector) => (0, 190, 0),
+            (_, PacketKind::TimeExceeded) => (255, 0, 255),
+        };
+        let position = Point::from(position);
+        renderer.packet_texture.set_color_mod(r, g, b);
+        renderer.canvas.copy(
+            &renderer.packet_texture,
+            None,
+            Some(Rect::from_center(position, 30, 30)),
+        )?;
+        renderer.packet_texture.set_color_mod(255, 255, 255);
+        let mark = match impairment {
+            Some(Impairment::Lost) => "x",
+            Some(Impairment::Corrupted) => "!",
+            Some(Impairment::Duplicate) => "+",
+            None => return Ok(()),
+        };
+        glyphs.draw(renderer, mark, position.x() + 8, position.y() - 20)?;
+        Ok(())
+    }
+
+    /// Draws the network, with cables colored by how busy they were lately if `heatmap` is set.
+    pub fn draw(
+        &self,
+        renderer: &mut Renderer,
+        glyphs: &Glyphs,
+        network: &Network,
+        heatmap: bool,
+    ) -> Result<(), String> {
+        for ether in network.ethers() {
+            let heat = heatmap.then(|| network.get_utilization(ether.get_id()));
+            for segment in ether.get_segments() {
+                Self::draw_cable(renderer, segment, ether.is_up(), heat)?;
+            }
+        }
+        for ether in network.ethers() {
+            let load = network.get_load(ether.get_id());
+            Self::draw_load(renderer, glyphs, &ether.get_segments(), load)?;
+        }
+
+        for node in network.nodes() {
+            self.draw_node(
+                renderer,
+                node.get_id(),
+                node.get_kind(),
+                node.get_position(),
+                node.get_autonomous_system(),
+                node.is_up(),
+            )?;
+        }
+
+        // Queue depths, drawn along each cable just outside the sending node.
+        for (_, _, queue) in network.queues() {
+            let from = queue.get_position();
+            let to = network
+                .ethers()
+                .find(|ether| ether.get_id() == queue.get_ether())
+                .and_then(|ether| ether.get_segments().first().copied())
+                .map(|(first, second)| if first == from { second } else { first });
+            let length = to.map_or(0.0, |to| distance_between((from, to)));
+            if let (Some(to), true) = (to, length > 0.0) {
+                let x = from.x() + ((to.x() - from.x()) as f64 * 40.0 / length) as i32;
+                let y = from.y() + ((to.y() - from.y()) as f64 * 40.0 / length) as i32;
+                let height = (queue.len() as u32 * 3).min(45);
+                renderer.canvas.set_draw_color(Color::RGB(255, 120, 0));
+                renderer
+                    .canvas
+                    .fill_rect(Rect::new(x - 3, y - height as i32, 6, height))?;
+                glyphs.draw(renderer, &queue.len().to_string(), x + 5, y - 10)?;
+            }
+        }
+
+        for transmission in network.transmissions() {
+            Self::draw_packet(
+                renderer,
+                glyphs,
+                transmission.get_position(network.get_time()),
+                transmission.get_packet().payload.kind(),
+                transmission.get_impairment(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws a recorded network the way `draw` draws a live one, queues aside.
+    pub fn draw_replay(
+        &self,
+        renderer: &mut Renderer,
+        glyphs: &Glyphs,
+        replay: &Replay,
+    ) -> Result<(), String> {
+        for ether in replay.ethers() {
+            for segment in ether.segments.iter() {
+                Self::draw_cable(renderer, *segment, ether.up, None)?;
+            }
+        }
+        for ether in replay.ethers() {
+            Self::draw_load(renderer, glyphs, &ether.segments, replay.get_load(ether.id))?;
+        }
+
+        for node in replay.nodes() {
+            self.draw_node(
+                renderer,
+                node.id,
+                node.kind,
+                node.position,
+                node.autonomous_system,
+                node.up,
+            )?;
+        }
+
+        for (position, transmission) in replay.transmissions() {
+            Self::draw_packet(
+                renderer,
+                glyphs,
+                position,
+                transmission.kind,
+                transmission.impairment,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The nodes a packet went through, up to where it is now if it's still in flight.
+    pub fn draw_path(
+        &self,
+        renderer: &mut Renderer,
+        network: &Network,
+        uuid: Uuid,
+    ) -> Result<(), String> {
+        let record = match network.get_packet_record(uuid) {
+            Some(record) => record,
+            None => return Ok(()),
+        };
+        let mut points: Vec<Point> = record
+            .path
+            .iter()
+            .filter_map(|hop| network.nodes().find(|node| node.get_id() == hop.node))
+            .map(|node| node.get_position().into())
+            .collect();
+        points.extend(
+            network
+                .transmissions()
+                .iter()
+                .filter(|transmission| transmission.get_packet().uuid == uuid)
+                .take(1)
+                .map(|transmission| Point::from(transmission.get_position(network.get_time()))),
+        );
+        renderer.canvas.set_draw_color(Color::RGB(255, 220, 0));
+        for line in points.windows(2) {
+            for offset in -2..=2 {
+                renderer
+                    .canvas
+                    .draw_line(line[0].offset(offset, 0), line[1].offset(offset, 0))?;
+                renderer
+                    .canvas
+                    .draw_line(line[0].offset(0, offset), line[1].offset(0, offset))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// What the heatmap colors mean, in the bottom right corner.
+    pub fn draw_heatmap_legend(
+        &self,
+        renderer: &mut Renderer,
+        glyphs: &Glyphs,
+    ) -> Result<(), String> {
+        let (width, height) = renderer.canvas.output_size()?;
+        let (x, y) = (width as i32 - 130, height as i32 - 45);
+        glyphs.draw(renderer, "UTILIZATION", x, y)?;
+        for offset in 0..100 {
+            renderer
+                .canvas
+                .set_draw_color(heat_color(offset as f64 / 99.0));
+            renderer.canvas.draw_line(
+                Point::new(x + offset, y + 18),
+                Point::new(x + offset, y + 26),
+            )?;
+        }
+        glyphs.draw(renderer, "0%", x, y + 28)?;
+        glyphs.draw(renderer, "100%", x + 100 - 8 * 4, y + 28)?;
+        Ok(())
+    }
+
+    /// A table of the flows in the bottom left corner, as many as fit.
+    pub fn draw_flows(
+        &self,
+        renderer: &mut Renderer,
+        glyphs: &Glyphs,
+        flows: &[FlowStatistics],
+    ) -> Result<(), String> {
+        let (_, height) = renderer.canvas.output_size()?;
+        let rows = flows.len().min(12);
+        let (x, mut y) = (10, height as i32 - 16 * (rows as i32 + 1) - 5);
+        glyphs.draw(
+            renderer,
+            "FLOW       SENT  DELIV  DROP   MIN  MEAN   P95  HOPS  STRETCH",
+            x,
+            y,
+        )?;
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        for flow in flows.iter().take(rows) {
+            y += 16;
+            let line = format!(
+                "{:3} > {:3} {:5} {:6} {:5} {:>5} {:>5} {:>5} {:>5} {:>8}",
+                flow.source,
+                flow.destination,
+                flow.sent,
+                flow.delivered,
+                flow.dropped,
+                or_dash(flow.min_latency.map(|latency| latency.to_string())),
+                or_dash(flow.mean_latency.map(|latency| format!("{:.0}", latency))),
+                or_dash(flow.p95_latency.map(|latency| latency.to_string())),
+                or_dash(flow.mean_hops.map(|hops| format!("{:.1}", hops))),
+                or_dash(flow.stretch.map(|stretch| format!("{:.2}", stretch))),
+            );
+            glyphs.draw(renderer, &line, x, y)?;
+        }
+        Ok(())
+    }
+
+    pub fn draw_routes(
+        &self,
+        renderer: &mut Renderer,
+        glyphs: &Glyphs,
+        network: &Network,
+        node: NodeId,
+    ) -> Result<(), String> {
+        let node = match network.nodes().find(|candidate| candidate.get_id() == node) {
+            Some(node) => node,
+            None => return Ok(()),
+        };
+        let (x, mut y) = (640, 5);
+        glyphs.draw(renderer, &format!("ROUTES OF {}", node.get_id()), x, y)?;
+        for route in node.get_routes() {
+            y += 16;
+            let interfaces = route.interfaces.join(",");
+            let mut line = match route.metric {
+                Some(metric) => format!("{:3} > {} ({})", route.destination, interfaces, metric),
+                None => format!("{:3} > {}", route.destination, interfaces),
+            };
+            if !route.as_path.is_empty() {
+                let as_path: Vec<String> = route.as_path.iter().map(u32::to_string).collect();
+                line += &format!(" [{}]", as_path.join(" "));
+            }
+            glyphs.draw(renderer, &line, x, y)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+#[cfg(feature = "sdl")]
+pub mod app;
+pub mod network;
+pub mod scenario;
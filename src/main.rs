@@ -1,12 +1,18 @@
 #![windows_subsystem = "windows"]
 
-mod app;
-mod network;
-
-use app::{scenario::Scenario, App};
-use clap;
 use std::fs::File;
-use std::io::{stdin, BufReader};
+use std::io::{stdin, stdout, BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+#[cfg(feature = "sdl")]
+use visualize_network::app::App;
+use visualize_network::network::event::{EventSink, JsonLinesSink};
+use visualize_network::network::pcap::Capture;
+use visualize_network::network::recording::{Recorder, Recording};
+use visualize_network::network::report::flows_to_csv;
+use visualize_network::network::routing::Routing;
+use visualize_network::network::{Network, SimTime, LATENCY_COST, ROUND_ROBIN, TIME_EXCEEDED};
+use visualize_network::scenario::Scenario;
 
 pub fn main() -> Result<(), String> {
     let matches = clap::App::new("Computer Network Visualizer")
@@ -27,17 +33,314 @@ pub fn main() -> Result<(), String> {
                 .conflicts_with("file")
                 .help("Read scenario from stdin"),
         )
+        .arg(
+            clap::Arg::new("time-scale")
+                .short('t')
+                .long("time-scale")
+                .takes_value(true)
+                .default_value("1.0")
+                .help("Milliseconds of simulated time played back per real millisecond"),
+        )
+        .arg(
+            clap::Arg::new("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed for all random decisions, overriding the scenario's own seed"),
+        )
+        .arg(
+            clap::Arg::new("routing")
+                .long("routing")
+                .takes_value(true)
+                .possible_values(["global", "distance-vector", "link-state", "path-vector"])
+                .help("Routing used by all nodes, overriding the scenario's own (with default settings)"),
+        )
+        .arg(
+            clap::Arg::new("ecmp")
+                .long("ecmp")
+                .takes_value(true)
+                .possible_values(["flow-hash", "round-robin"])
+                .default_value("flow-hash")
+                .help("How packets are spread over equal-cost routes"),
+        )
+        .arg(
+            clap::Arg::new("cost")
+                .long("cost")
+                .takes_value(true)
+                .possible_values(["distance", "latency"])
+                .default_value("distance")
+                .help("What global routing minimizes: drawn cable length or transmission time"),
+        )
+        .arg(
+            clap::Arg::new("time-exceeded")
+                .long("time-exceeded")
+                .help("Answer packets whose TTL runs out with a time exceeded notice to their source"),
+        )
+        .arg(
+            clap::Arg::new("events")
+                .long("events")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("Write what happens to every packet as JSON lines to a file ('-' for stdout)"),
+        )
+        .arg(
+            clap::Arg::new("record")
+                .long("record")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("Record the run to a file that --replay can play back"),
+        )
+        .arg(
+            clap::Arg::new("pcap")
+                .long("pcap")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("Write the packets sent over each link to a pcap file in this directory"),
+        )
+        .arg(
+            clap::Arg::new("flows")
+                .long("flows")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .help("Write per-flow delivery statistics to a file when the run ends (CSV if it ends in .csv, JSON otherwise)"),
+        )
+        .arg(
+            clap::Arg::new("replay")
+                .long("replay")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .conflicts_with_all(&["file", "stdin", "headless", "record", "events", "pcap", "flows"])
+                .help("Play a recorded run back in the window instead of simulating"),
+        )
+        .arg(
+            clap::Arg::new("headless")
+                .long("headless")
+                .help("Run the scenario to completion without a window and print a report"),
+        )
+        .arg(
+            clap::Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(["text", "json"])
+                .default_value("text")
+                .requires("headless")
+                .help("Format of the headless report"),
+        )
+        .arg(
+            clap::Arg::new("max-time")
+                .long("max-time")
+                .takes_value(true)
+                .requires("headless")
+                .help("Stop the headless run after this many milliseconds of simulated time"),
+        )
         .get_matches();
 
+    let time_scale: f64 = matches
+        .value_of_t("time-scale")
+        .map_err(|err| format!("Invalid time scale: {}", err))?;
+    let seed: Option<u64> = if matches.is_present("seed") {
+        Some(
+            matches
+                .value_of_t("seed")
+                .map_err(|err| format!("Invalid seed: {}", err))?,
+        )
+    } else {
+        None
+    };
+
+    ROUND_ROBIN.store(
+        matches.value_of("ecmp") == Some("round-robin"),
+        Ordering::Relaxed,
+    );
+    LATENCY_COST.store(
+        matches.value_of("cost") == Some("latency"),
+        Ordering::Relaxed,
+    );
+    TIME_EXCEEDED.store(matches.is_present("time-exceeded"), Ordering::Relaxed);
+
+    let routing = matches.value_of("routing").map(|routing| match routing {
+        "distance-vector" => Routing::DistanceVector(Default::default()),
+        "link-state" => Routing::LinkState(Default::default()),
+        "path-vector" => Routing::PathVector(Default::default()),
+        _ => Routing::Global,
+    });
+
+    let events_to_stdout = matches.value_of_os("events") == Some("-".as_ref());
+    let events: Option<Box<dyn EventSink>> = match matches.value_of_os("events") {
+        Some(path) if path == "-" => Some(Box::new(JsonLinesSink::new(stdout()))),
+        Some(path) => {
+            let file =
+                File::create(path).map_err(|err| format!("Cannot create event log: {}", err))?;
+            Some(Box::new(JsonLinesSink::new(BufWriter::new(file))))
+        }
+        None => None,
+    };
+
+    if let Some(path) = matches.value_of_os("replay") {
+        let file = File::open(path).map_err(|err| format!("Cannot read recording: {}", err))?;
+        let recording = Recording::load(BufReader::new(file))?;
+        return run_replay(time_scale, recording);
+    }
+
+    let recorder = match matches.value_of_os("record") {
+        Some(path) => {
+            let file =
+                File::create(path).map_err(|err| format!("Cannot create recording: {}", err))?;
+            Some(Recorder::new(BufWriter::new(file)))
+        }
+        None => None,
+    };
+
+    let capture = match matches.value_of_os("pcap") {
+        Some(path) => {
+            Some(Capture::new(path).map_err(|err| format!("Cannot create capture: {}", err))?)
+        }
+        None => None,
+    };
+
+    let mut outputs = Outputs {
+        events,
+        recorder,
+        capture,
+        flows: matches.value_of_os("flows").map(PathBuf::from),
+    };
+
     let scenario = if matches.is_present("stdin") {
         let reader = BufReader::new(stdin());
-        Some(Scenario::load(reader).expect("Unable to load scenario from file!"))
+        Some(Scenario::load(reader).map_err(|err| format!("Cannot load scenario: {}", err))?)
     } else {
-        matches.value_of_os("file").and_then(|path| {
-            let file = File::open(path).expect("Cannot read file!");
-            let reader = BufReader::new(file);
-            Some(Scenario::load(reader).expect("Unable to load scenario from file!"))
-        })
+        match matches.value_of_os("file") {
+            Some(path) => {
+                let file =
+                    File::open(path).map_err(|err| format!("Cannot read scenario: {}", err))?;
+                let reader = BufReader::new(file);
+                Some(
+                    Scenario::load(reader)
+                        .map_err(|err| format!("Cannot load scenario: {}", err))?,
+                )
+            }
+            None => None,
+        }
     };
-    App::new()?.run(scenario)
+
+    if let Some(Err(errors)) = scenario.as_ref().map(Scenario::validate) {
+        for error in errors.iter() {
+            eprintln!("{}", error);
+        }
+        return Err(format!(
+            "Invalid scenario: {} problem(s) found",
+            errors.len()
+        ));
+    }
+
+    if matches.is_present("headless") {
+        let time_limit: Option<SimTime> = if matches.is_present("max-time") {
+            Some(
+                matches
+                    .value_of_t("max-time")
+                    .map_err(|err| format!("Invalid time limit: {}", err))?,
+            )
+        } else {
+            None
+        };
+
+        let mut scenario = scenario.unwrap_or_default();
+        if seed.is_some() {
+            scenario.seed = seed;
+        }
+        if let Some(routing) = routing {
+            scenario.routing = routing;
+        }
+        let mut network = Network::new();
+        outputs.attach(&mut network);
+        scenario.build(&mut network)?;
+        network.run_until_idle(time_limit)?;
+        outputs.finish(&network)?;
+
+        let report = network.report();
+        let report = match matches.value_of("format") {
+            Some("json") => report.to_json()? + "\n",
+            _ => report.to_string(),
+        };
+        // Events written to stdout stay parseable on their own.
+        if events_to_stdout {
+            eprint!("{}", report);
+        } else {
+            print!("{}", report);
+        }
+        return Ok(());
+    }
+
+    run_visualizer(time_scale, seed, routing, scenario, outputs)
+}
+
+/// Where a run writes what happened in it, besides the report.
+struct Outputs {
+    events: Option<Box<dyn EventSink>>,
+    recorder: Option<Recorder>,
+    capture: Option<Capture>,
+    flows: Option<PathBuf>,
+}
+
+impl Outputs {
+    fn attach(&mut self, network: &mut Network) {
+        if let Some(events) = self.events.take() {
+            network.set_event_sink(events);
+        }
+        if let Some(recorder) = self.recorder.take() {
+            network.set_recorder(recorder);
+        }
+        if let Some(capture) = self.capture.take() {
+            network.set_capture(capture);
+        }
+    }
+
+    fn finish(&self, network: &Network) -> Result<(), String> {
+        if let Some(path) = &self.flows {
+            let flows = network.flow_statistics();
+            let contents = if path.extension().is_some_and(|extension| extension == "csv") {
+                flows_to_csv(&flows)
+            } else {
+                serde_json::to_string_pretty(&flows)
+                    .map_err(|err| format!("Serde error: {}", err))?
+            };
+            std::fs::write(path, contents)
+                .map_err(|err| format!("Cannot write flow statistics: {}", err))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn run_visualizer(
+    time_scale: f64,
+    seed: Option<u64>,
+    routing: Option<Routing>,
+    scenario: Option<Scenario>,
+    mut outputs: Outputs,
+) -> Result<(), String> {
+    let mut app = App::new(time_scale, seed, routing)?;
+    outputs.attach(&mut app.network);
+    app.run(scenario)?;
+    outputs.finish(&app.network)
+}
+
+#[cfg(feature = "sdl")]
+fn run_replay(time_scale: f64, recording: Recording) -> Result<(), String> {
+    App::new(time_scale, None, None)?.replay(recording)
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_visualizer(
+    _: f64,
+    _: Option<u64>,
+    _: Option<Routing>,
+    _: Option<Scenario>,
+    _: Outputs,
+) -> Result<(), String> {
+    Err("Built without the `sdl` feature: only --headless runs are available".to_string())
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_replay(_: f64, _: Recording) -> Result<(), String> {
+    Err("Built without the `sdl` feature: recordings can't be replayed".to_string())
 }
@@ -0,0 +1,51 @@
+use super::ether::EtherId;
+use super::node::{NodeId, NodeInterfaceId};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkError {
+    NodeNotFound(NodeId),
+    EtherNotFound(EtherId),
+    InterfaceNotFound(NodeId, NodeInterfaceId),
+    InterfaceAlreadyExists(NodeId, NodeInterfaceId),
+    WrongSidesCount(EtherId, usize),
+    EventSink(String),
+    Recording(String),
+    Capture(String),
+    /// Every problem found in a scenario.
+    InvalidScenario(Vec<String>),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::NodeNotFound(node) => write!(f, "Node '{}' not found!", node),
+            NetworkError::EtherNotFound(ether) => write!(f, "Ether '{}' not found!", ether),
+            NetworkError::InterfaceNotFound(node, interface) => {
+                write!(f, "No interface '{}' in node '{}'!", interface, node)
+            }
+            NetworkError::InterfaceAlreadyExists(node, interface) => write!(
+                f,
+                "Interface '{}' already created in node '{}'!",
+                interface, node
+            ),
+            NetworkError::WrongSidesCount(ether, count) => {
+                write!(f, "Ether '{}' cannot connect {} interfaces!", ether, count)
+            }
+            NetworkError::EventSink(error) => write!(f, "Cannot record event: {}", error),
+            NetworkError::Recording(error) => write!(f, "Cannot write recording: {}", error),
+            NetworkError::Capture(error) => write!(f, "Cannot write capture: {}", error),
+            NetworkError::InvalidScenario(errors) => {
+                write!(f, "Invalid scenario: {}", errors.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<NetworkError> for String {
+    fn from(error: NetworkError) -> String {
+        error.to_string()
+    }
+}
@@ -1,12 +1,14 @@
-use super::super::ether::{Ether, EtherId, EtherInterface};
-use crate::app::Renderer;
+use super::super::error::NetworkError;
+use super::super::ether::{Ether, EtherId, EtherInterface, LinkAttributes};
 use crate::network::node::{Node, NodeId, NodeInterfaceId};
-use sdl2::rect::Point;
+use crate::network::Point;
 
 pub struct Cable {
     id: EtherId,
     sides: Option<[EtherInterface; 2]>,
     cached_positions: Option<[Point; 2]>,
+    attributes: LinkAttributes,
+    up: bool,
 }
 
 impl Ether for Cable {
@@ -14,60 +16,86 @@ impl Ether for Cable {
         self.id
     }
 
-    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
-        match self.cached_positions {
-            Some(cached_positions) => renderer
-                .canvas
-                .draw_line(cached_positions[0], cached_positions[1]),
-            None => Ok(()),
+    fn connect_internal(&mut self, interfaces: Vec<EtherInterface>) -> Result<(), NetworkError> {
+        let count = interfaces.len();
+        let sides: [EtherInterface; 2] = interfaces
+            .try_into()
+            .map_err(|_| NetworkError::WrongSidesCount(self.get_id(), count))?;
+        self.sides = Some(sides);
+        Ok(())
+    }
+
+    fn connect(
+        &mut self,
+        interfaces: Vec<(&dyn Node, NodeInterfaceId)>,
+    ) -> Result<(), NetworkError> {
+        if interfaces.len() != 2 {
+            return Err(NetworkError::WrongSidesCount(
+                self.get_id(),
+                interfaces.len(),
+            ));
         }
+        for (node, interface) in interfaces.iter() {
+            if node.get_interface(interface.clone()).is_none() {
+                return Err(NetworkError::InterfaceNotFound(
+                    node.get_id(),
+                    interface.clone(),
+                ));
+            }
+        }
+
+        let mut cached_positions = vec![];
+        let mut sides = vec![];
+        for (node, interface) in interfaces.iter() {
+            cached_positions.push(node.get_position());
+            node.connect_interface(interface.clone(), self.get_id())?;
+            sides.push(EtherInterface::from_node_interface(
+                node.get_interface(interface.clone()).unwrap(),
+            ));
+        }
+        self.connect_internal(sides)?;
+
+        self.cached_positions = Some([cached_positions[0], cached_positions[1]]);
+        Ok(())
     }
 
-    fn connect_internal(&mut self, mut interfaces: Vec<EtherInterface>) {
-        assert_eq!(interfaces.len(), 2);
+    fn get_interfaces(&self) -> Vec<EtherInterface> {
+        self.sides
+            .as_ref()
+            .map_or_else(Vec::new, |sides| sides.to_vec())
+    }
 
-        let mut interfaces = interfaces.drain(..);
-        let interface_1 = interfaces.next().unwrap();
-        let interface_2 = interfaces.next().unwrap();
-        self.sides = Some([interface_1, interface_2]);
+    fn get_directions(&self) -> Vec<(NodeId, NodeId)> {
+        match self.sides.as_ref() {
+            Some([first, second]) => vec![
+                (first.owner_node, second.owner_node),
+                (second.owner_node, first.owner_node),
+            ],
+            None => vec![],
+        }
     }
 
-    fn connect(&mut self, interfaces: Vec<(&Box<dyn Node>, NodeInterfaceId)>) {
-        let mut cached_positions = vec![];
+    fn get_segments(&self) -> Vec<(Point, Point)> {
+        match self.cached_positions {
+            Some(cached_positions) => vec![(cached_positions[0], cached_positions[1])],
+            None => vec![],
+        }
+    }
 
-        self.connect_internal(
-            interfaces
-                .iter()
-                .map(|(node, interface)| {
-                    cached_positions.push(node.get_position());
-                    node.connect_interface(interface.clone(), self.get_id());
-                    EtherInterface::from_node_interface(node.get_interface(interface.clone()))
-                })
-                .collect(),
-        );
+    fn get_attributes(&self) -> &LinkAttributes {
+        &self.attributes
+    }
 
-        let mut cached_positions = cached_positions.drain(..);
-        self.cached_positions = Some([
-            cached_positions.next().unwrap(),
-            cached_positions.next().unwrap(),
-        ]);
+    fn set_attributes(&mut self, attributes: LinkAttributes) {
+        self.attributes = attributes;
     }
 
-    fn get_interfaces(&self) -> Vec<EtherInterface> {
-        self.sides.as_ref().unwrap().to_vec()
+    fn is_up(&self) -> bool {
+        self.up
     }
 
-    fn get_distance_multipliers(&self) -> Vec<(NodeId, NodeId, f64)> {
-        let mut distances = vec![];
-        if let Some(sides) = self.sides.as_ref() {
-            let nodes = (
-                sides.get(0).unwrap().owner_node,
-                sides.get(1).unwrap().owner_node,
-            );
-            distances.push((nodes.0, nodes.1, 1.0));
-            distances.push((nodes.1, nodes.0, 1.0));
-        }
-        distances
+    fn set_up(&mut self, up: bool) {
+        self.up = up;
     }
 }
 
@@ -77,6 +105,8 @@ impl Cable {
             id,
             sides: None,
             cached_positions: None,
+            attributes: LinkAttributes::default(),
+            up: true,
         }
     }
 }
@@ -1,19 +1,82 @@
 pub mod cable;
 
+use super::error::NetworkError;
 use super::node::{Node, NodeId, NodeInterface, NodeInterfaceId};
-use crate::app::Renderer;
-use sdl2::rect::Point;
+use super::queue::QueueSettings;
+use super::{Point, SimTime, PIXELS_PER_MS, TICK};
 use std::cell::Cell;
 
 pub type EtherId = usize;
 
 pub trait Ether {
     fn get_id(&self) -> EtherId;
-    fn draw(&self, renderer: &mut Renderer) -> Result<(), String>;
-    fn connect_internal(&mut self, interfaces: Vec<EtherInterface>);
-    fn connect(&mut self, interfaces: Vec<(&Box<dyn Node>, NodeInterfaceId)>);
+    fn connect_internal(&mut self, interfaces: Vec<EtherInterface>) -> Result<(), NetworkError>;
+    fn connect(
+        &mut self,
+        interfaces: Vec<(&dyn Node, NodeInterfaceId)>,
+    ) -> Result<(), NetworkError>;
     fn get_interfaces(&self) -> Vec<EtherInterface>;
-    fn get_distance_multipliers(&self) -> Vec<(NodeId, NodeId, f64)>;
+    /// `(from, to)` pairs of nodes the ether carries packets between.
+    fn get_directions(&self) -> Vec<(NodeId, NodeId)>;
+    fn get_segments(&self) -> Vec<(Point, Point)>;
+    fn get_attributes(&self) -> &LinkAttributes;
+    fn set_attributes(&mut self, attributes: LinkAttributes);
+    fn is_up(&self) -> bool;
+    fn set_up(&mut self, up: bool);
+}
+
+#[derive(Clone, Default)]
+pub struct LinkAttributes {
+    /// Propagation delay in milliseconds; derived from the drawn length when absent.
+    pub latency: Option<SimTime>,
+    /// Bytes per millisecond; unlimited when absent.
+    pub bandwidth: Option<f64>,
+    pub impairments: Impairments,
+    /// Output queues of the link's interfaces; the network's own settings when absent.
+    pub queue: Option<QueueSettings>,
+}
+
+/// Ways a link mistreats the packets it carries.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Impairments {
+    /// Probability of losing each packet sent over the link.
+    pub loss: f64,
+    /// Probability of damaging a packet, which the receiver then discards.
+    pub corruption: f64,
+    /// Probability of delivering a packet twice.
+    pub duplication: f64,
+    /// Upper bound of a random extra delay, in ms; packets may overtake each other.
+    pub jitter: SimTime,
+}
+
+impl Impairments {
+    /// `(name, value)` pairs of the probabilities that are out of range.
+    pub fn invalid_probabilities(&self) -> Vec<(&'static str, f64)> {
+        [
+            ("loss", self.loss),
+            ("corruption", self.corruption),
+            ("duplication", self.duplication),
+        ]
+        .into_iter()
+        .filter(|(_, probability)| !(0.0..=1.0).contains(probability))
+        .collect()
+    }
+}
+
+impl LinkAttributes {
+    pub fn propagation_delay(&self, from: Point, to: Point) -> SimTime {
+        self.latency
+            .unwrap_or_else(|| (distance_between((from, to)) / PIXELS_PER_MS) as SimTime)
+    }
+
+    pub fn serialization_delay(&self, size: u64) -> SimTime {
+        self.bandwidth
+            .map_or(0, |bandwidth| (size as f64 / bandwidth).ceil() as SimTime)
+    }
+
+    pub fn transmission_time(&self, from: Point, to: Point, size: u64) -> SimTime {
+        (self.propagation_delay(from, to) + self.serialization_delay(size)).max(TICK)
+    }
 }
 
 impl dyn Ether {
@@ -57,3 +120,19 @@ pub fn distance_between(points: (Point, Point)) -> f64 {
     let vector = points.0 - points.1;
     ((vector.x().pow(2) + vector.y().pow(2)) as f64).sqrt()
 }
+
+pub fn distance_to_segment(point: Point, segment: (Point, Point)) -> f64 {
+    let (from, to) = segment;
+    let (along, across) = (to - from, point - from);
+    let length = along.x().pow(2) + along.y().pow(2);
+    if length == 0 {
+        return distance_between((point, from));
+    }
+    let t =
+        ((across.x() * along.x() + across.y() * along.y()) as f64 / length as f64).clamp(0.0, 1.0);
+    let (x, y) = (
+        from.x() as f64 + t * along.x() as f64,
+        from.y() as f64 + t * along.y() as f64,
+    );
+    ((point.x() as f64 - x).powi(2) + (point.y() as f64 - y).powi(2)).sqrt()
+}
@@ -0,0 +1,115 @@
+use super::ether::EtherId;
+use super::node::{NodeId, NodeInterfaceId};
+use super::SimTime;
+use serde::Serialize;
+use std::io::{self, Write};
+use uuid::Uuid;
+
+/// Why a data packet was lost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropReason {
+    /// The output queue was full.
+    QueueFull,
+    /// Random early detection discarded it before the queue filled up.
+    EarlyDrop,
+    LinkDown,
+    NodeDown,
+    /// The node, or one of the packet's ends, was removed.
+    NodeRemoved,
+    /// The receiving node had nowhere to forward it.
+    NoRoute,
+    /// Lost on a link.
+    Lost,
+    /// Damaged on a link and discarded by the receiver.
+    Corrupted,
+    /// Ran out of TTL.
+    Expired,
+}
+
+/// Something that happened to a data packet.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Send {
+        uuid: Uuid,
+        source: NodeId,
+        destination: NodeId,
+        size: u64,
+        ttl: u8,
+    },
+    Enqueue {
+        uuid: Uuid,
+        node: NodeId,
+        interface: NodeInterfaceId,
+        depth: usize,
+    },
+    TransmitStart {
+        uuid: Uuid,
+        node: NodeId,
+        interface: NodeInterfaceId,
+        ether: EtherId,
+        recipient: NodeId,
+        duration: SimTime,
+    },
+    Receive {
+        uuid: Uuid,
+        node: NodeId,
+        interface: NodeInterfaceId,
+    },
+    Forward {
+        uuid: Uuid,
+        node: NodeId,
+        interface: NodeInterfaceId,
+        ttl: u8,
+    },
+    Deliver {
+        uuid: Uuid,
+        node: NodeId,
+    },
+    Drop {
+        uuid: Uuid,
+        node: Option<NodeId>,
+        reason: DropReason,
+    },
+    /// A copy of a packet duplicated by a link came to an end without deciding what became of
+    /// the packet: it was delivered after another copy, or dropped while another one was left.
+    Duplicate {
+        uuid: Uuid,
+        node: Option<NodeId>,
+        /// Why the copy was dropped, if it wasn't delivered.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<DropReason>,
+    },
+}
+
+/// Where the network reports what happens to data packets, in simulated time order.
+pub trait EventSink {
+    fn record(&mut self, time: SimTime, event: &Event) -> io::Result<()>;
+}
+
+/// Writes every event as a JSON object on its own line, e.g.
+/// `{"time":96,"event":"receive","uuid":"...","node":1,"interface":"1-0"}`.
+pub struct JsonLinesSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(writer: W) -> JsonLinesSink<W> {
+        JsonLinesSink { writer }
+    }
+}
+
+#[derive(Serialize)]
+struct TimedEvent<'a> {
+    time: SimTime,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+impl<W: Write> EventSink for JsonLinesSink<W> {
+    fn record(&mut self, time: SimTime, event: &Event) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, &TimedEvent { time, event })?;
+        self.writer.write_all(b"\n")
+    }
+}
@@ -1,50 +1,197 @@
+pub mod error;
 pub mod ether;
+pub mod event;
 pub mod node;
 pub mod packet;
+pub mod pcap;
+pub mod queue;
+pub mod recording;
+pub mod report;
+pub mod routing;
 
-use crate::app::Renderer;
 use crate::network::node::endpoint_node::EndpointNode;
+use error::NetworkError;
 use ether::cable::Cable;
-use ether::{distance_between, Ether, EtherId, EtherInterface};
+use ether::{
+    distance_between, distance_to_segment, Ether, EtherId, EtherInterface, Impairments,
+    LinkAttributes,
+};
+use event::{DropReason, Event, EventSink};
 use indexmap::IndexMap;
-use node::{router_node::RouterNode, Node, NodeId, NodeInterfaceId};
-use packet::Packet;
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
+use node::{router_node::RouterNode, AsNumber, Node, NodeId, NodeInterfaceId, LOCALHOST};
+use packet::{random_uuid, Hop, Packet, Payload, PACKET_SIZE};
+use pcap::Capture;
+use queue::{OutputQueue, QueueSettings};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use recording::{
+    RecordedEther, RecordedEvent, RecordedNode, RecordedTransmission, Recorder, RecordingHeader,
+};
+use report::{FlowStatistics, PacketRecord, PacketStatus, Report};
+use routing::{Cost, Graph, Routing, ShortestPathTree};
+use serde::{Deserialize, Serialize};
 use slab::Slab;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ops::Sub;
+use std::sync::atomic::{AtomicBool, Ordering};
 use uuid::Uuid;
 
+pub static DIJKSTRA: AtomicBool = AtomicBool::new(true);
+pub static BACK: AtomicBool = AtomicBool::new(false);
+pub static DELETE: AtomicBool = AtomicBool::new(false);
+/// Spread packets over equal-cost routes in turn instead of by a hash of their flow.
+pub static ROUND_ROBIN: AtomicBool = AtomicBool::new(false);
+/// Answer packets whose TTL runs out with a time exceeded notice to their source.
+pub static TIME_EXCEEDED: AtomicBool = AtomicBool::new(false);
+/// Make global routing minimize the links' transmission time instead of their drawn length.
+pub static LATENCY_COST: AtomicBool = AtomicBool::new(false);
+
+/// Simulated time in milliseconds.
+pub type SimTime = u64;
+
+/// Simulated time advanced by a single `Network::step`.
+pub const TICK: SimTime = 1;
+
+/// How far a packet travels along a cable during one millisecond of simulated time.
+pub const PIXELS_PER_MS: f64 = 1.0;
+
+/// Span over which the time each ether spent serializing packets is summed up to tell how
+/// busy it is.
+pub const UTILIZATION_WINDOW: SimTime = 1000;
+
+/// Something the network does at a given simulated time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScheduledAction {
+    Send {
+        uuid: Uuid,
+        source: NodeId,
+        destination: NodeId,
+        size: u64,
+        ttl: u8,
+    },
+    LinkDown(EtherId),
+    LinkUp(EtherId),
+    NodeFail(NodeId),
+    NodeRestart(NodeId),
+    NodeRemove(NodeId),
+    Impair(EtherId, Impairments),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    pub fn x(&self) -> i32 {
+        self.x
+    }
+
+    pub fn y(&self) -> i32 {
+        self.y
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
 pub struct Network {
     nodes: Slab<Box<dyn Node>>,
     ethers: Slab<Box<dyn Ether>>,
     transmissions: Vec<Transmission>,
     incoming: Vec<(NodeId, NodeInterfaceId, Packet)>,
-    outgoing: Vec<(EtherId, Point, Packet)>,
+    outgoing: Vec<((NodeId, NodeInterfaceId), EtherId, Point, Packet)>,
+    /// Packets waiting for their interface's link, by sending interface.
+    queues: BTreeMap<(NodeId, NodeInterfaceId), OutputQueue>,
+    queue_settings: QueueSettings,
+    scheduled: VecDeque<(SimTime, ScheduledAction)>,
+    packets: IndexMap<Uuid, PacketRecord>,
+    /// Bumped whenever a data packet is sent or comes to an end.
+    packet_updates: u64,
+    /// Shortest paths in hops from the nodes packets were sent from, in the current topology.
+    hop_trees: HashMap<NodeId, ShortestPathTree>,
+    /// Extra copies of data packets made by links that haven't come to an end yet.
+    copies: HashMap<Uuid, usize>,
+    /// Data packets sent over each ether so far.
+    loads: HashMap<EtherId, u64>,
+    /// When each node was serializing packets of any kind onto each ether lately.
+    busy: HashMap<(EtherId, NodeId), BusyTime>,
+    time: SimTime,
+    seed: u64,
+    rng: StdRng,
+    routing: Routing,
+    event_sink: Option<Box<dyn EventSink>>,
+    /// Events waiting to be recorded at the end of the step.
+    events: Vec<(SimTime, Event)>,
+    recorder: Option<Recorder>,
+    /// Changes waiting to be written to the recording at the end of the step.
+    recorded: Vec<(SimTime, RecordedEvent)>,
+    next_transmission: u64,
+    capture: Option<Capture>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network::new()
+    }
 }
 
 impl Network {
     pub fn new() -> Network {
+        let seed = rand::thread_rng().gen();
         Network {
             nodes: Slab::new(),
             ethers: Slab::new(),
             transmissions: vec![],
             incoming: vec![],
             outgoing: vec![],
+            queues: BTreeMap::new(),
+            queue_settings: QueueSettings::default(),
+            scheduled: VecDeque::new(),
+            packets: IndexMap::new(),
+            packet_updates: 0,
+            hop_trees: HashMap::new(),
+            copies: HashMap::new(),
+            loads: HashMap::new(),
+            busy: HashMap::new(),
+            time: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            routing: Routing::Global,
+            event_sink: None,
+            events: vec![],
+            recorder: None,
+            recorded: vec![],
+            next_transmission: 0,
+            capture: None,
         }
     }
 
-    pub fn add_router_node(&mut self, renderer: &mut Renderer, position: Point) -> NodeId {
+    pub fn add_router_node(&mut self, position: Point) -> NodeId {
         let entry = self.nodes.vacant_entry();
         let id = entry.key();
-        entry.insert(Box::new(RouterNode::new(renderer, id, position)) as Box<dyn Node>);
+        let mut node = Box::new(RouterNode::new(id, position)) as Box<dyn Node>;
+        node.set_routing_protocol(self.routing.create_protocol());
+        entry.insert(node);
         id
     }
 
-    pub fn add_endpoint_node(&mut self, renderer: &mut Renderer, position: Point) -> NodeId {
+    pub fn add_endpoint_node(&mut self, position: Point) -> NodeId {
         let entry = self.nodes.vacant_entry();
         let id = entry.key();
-        entry.insert(Box::new(EndpointNode::new(renderer, id, position)) as Box<dyn Node>);
+        let mut node = Box::new(EndpointNode::new(id, position)) as Box<dyn Node>;
+        node.set_routing_protocol(self.routing.create_protocol());
+        entry.insert(node);
         id
     }
 
@@ -52,27 +199,54 @@ impl Network {
         &mut self,
         node: NodeId,
         interface: NodeInterfaceId,
-    ) -> Result<NodeInterfaceId, String> {
+    ) -> Result<NodeInterfaceId, NetworkError> {
         self.nodes
             .get_mut(node)
-            .expect(&*format!("Node '{}' not found!", node))
+            .ok_or(NetworkError::NodeNotFound(node))?
             .create_interface(interface)
     }
 
-    pub fn add_cable(&mut self) -> EtherId {
-        let entry = self.ethers.vacant_entry();
-        let id = entry.key();
-        entry.insert(Box::new(Cable::new(id)) as Box<dyn Ether>);
-        id
+    pub fn set_autonomous_system(
+        &mut self,
+        node: NodeId,
+        autonomous_system: Option<AsNumber>,
+    ) -> Result<(), NetworkError> {
+        self.nodes
+            .get_mut(node)
+            .ok_or(NetworkError::NodeNotFound(node))?
+            .set_autonomous_system(autonomous_system);
+        Ok(())
     }
 
-    pub fn connect_cable(&mut self, sides: ((NodeId, NodeInterfaceId), (NodeId, NodeInterfaceId))) {
-        let cable = self.add_cable();
+    pub fn connect_cable(
+        &mut self,
+        sides: ((NodeId, NodeInterfaceId), (NodeId, NodeInterfaceId)),
+        attributes: LinkAttributes,
+    ) -> Result<EtherId, NetworkError> {
         let (s1, s2) = sides;
-        self.ethers.get_mut(cable).unwrap().connect(vec![
-            (self.nodes.get(s1.0).unwrap(), s1.1),
-            (self.nodes.get(s2.0).unwrap(), s2.1),
-        ])
+        let node1 = self
+            .nodes
+            .get(s1.0)
+            .ok_or(NetworkError::NodeNotFound(s1.0))?;
+        let node2 = self
+            .nodes
+            .get(s2.0)
+            .ok_or(NetworkError::NodeNotFound(s2.0))?;
+        let entry = self.ethers.vacant_entry();
+        let cable = entry.key();
+        let mut ether = Box::new(Cable::new(cable)) as Box<dyn Ether>;
+        ether.connect(vec![(node1.as_ref(), s1.1), (node2.as_ref(), s2.1)])?;
+        ether.set_attributes(attributes);
+        entry.insert(ether);
+        self.hop_trees.clear();
+        Ok(cable)
+    }
+
+    fn get_node(&self, node: NodeId) -> Result<&dyn Node, NetworkError> {
+        self.nodes
+            .get(node)
+            .map(|node| node.as_ref())
+            .ok_or(NetworkError::NodeNotFound(node))
     }
 
     pub fn locate_node(&self, position: Point) -> Option<NodeId> {
@@ -84,211 +258,1498 @@ impl Network {
         None
     }
 
-    pub fn send(&mut self, uuid: Uuid, source: NodeId, destination: NodeId) {
+    pub fn locate_ether(&self, position: Point) -> Option<EtherId> {
+        for (id, ether) in self.ethers.iter() {
+            for segment in ether.get_segments() {
+                if distance_to_segment(position, segment) < 6.0 {
+                    return Some(id);
+                }
+            }
+        }
+        None
+    }
+
+    /// The in-flight data packet drawn around the position, if any.
+    pub fn locate_packet(&self, position: Point) -> Option<Uuid> {
+        self.transmissions
+            .iter()
+            .filter(|transmission| transmission.packet.is_data())
+            .find(|transmission| {
+                distance_between((position, transmission.get_position(self.time))) < 8.0
+            })
+            .map(|transmission| transmission.packet.uuid)
+    }
+
+    pub fn get_packet_record(&self, uuid: Uuid) -> Option<&PacketRecord> {
+        self.packets.get(&uuid)
+    }
+
+    pub fn is_link_up(&self, ether: EtherId) -> Result<bool, NetworkError> {
+        self.ethers
+            .get(ether)
+            .map(|ether| ether.is_up())
+            .ok_or(NetworkError::EtherNotFound(ether))
+    }
+
+    /// Takes an ether down or brings it back up. Packets on a downed ether are lost, and
+    /// global routes are recalculated; routing protocols notice by themselves.
+    pub fn set_link_up(&mut self, ether: EtherId, up: bool) -> Result<(), NetworkError> {
+        let ether_ref = self
+            .ethers
+            .get_mut(ether)
+            .ok_or(NetworkError::EtherNotFound(ether))?;
+        if ether_ref.is_up() == up {
+            return Ok(());
+        }
+        ether_ref.set_up(up);
+        for interface in ether_ref.get_interfaces() {
+            let (owner, owner_interface) = interface.get_to_owner();
+            self.get_node(owner)?
+                .get_interface(owner_interface.clone())
+                .ok_or(NetworkError::InterfaceNotFound(owner, owner_interface))?
+                .set_link_up(up);
+        }
+
+        self.hop_trees.clear();
+        self.record(RecordedEvent::LinkState { ether, up });
+        if !up {
+            self.drop_traffic(ether, DropReason::LinkDown);
+        }
+
+        match self.routing {
+            Routing::Global => self.calculate_routes(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Changes how an ether mistreats packets; those already on it are not affected.
+    pub fn set_impairments(
+        &mut self,
+        ether: EtherId,
+        impairments: Impairments,
+    ) -> Result<(), NetworkError> {
+        let ether = self
+            .ethers
+            .get_mut(ether)
+            .ok_or(NetworkError::EtherNotFound(ether))?;
+        let mut attributes = ether.get_attributes().clone();
+        attributes.impairments = impairments;
+        ether.set_attributes(attributes);
+        Ok(())
+    }
+
+    /// Loses the packets on an ether along with those queued for it.
+    fn drop_traffic(&mut self, ether: EtherId, reason: DropReason) {
+        let (mut lost, mut ended) = (vec![], vec![]);
+        self.transmissions.retain(|transmission| {
+            if transmission.ether != ether {
+                return true;
+            }
+            lost.push((transmission.packet.uuid, None));
+            ended.push(RecordedEvent::TransmissionEnd {
+                id: transmission.id,
+            });
+            false
+        });
+        for event in ended {
+            self.record(event);
+        }
+        self.queues.retain(|(owner, _), queue| {
+            if queue.get_ether() != ether {
+                return true;
+            }
+            lost.extend(
+                queue
+                    .clear()
+                    .into_iter()
+                    .map(|packet| (packet.uuid, Some(*owner))),
+            );
+            false
+        });
+        for (uuid, node) in lost {
+            self.drop_packet(uuid, node, reason);
+        }
+    }
+
+    pub fn is_node_up(&self, node: NodeId) -> Result<bool, NetworkError> {
+        Ok(self.get_node(node)?.is_up())
+    }
+
+    /// Fails a node, which then loses every packet reaching it, or restarts it with
+    /// no routes and fresh routing protocol state.
+    pub fn set_node_up(&mut self, node: NodeId, up: bool) -> Result<(), NetworkError> {
+        let node_ref = self
+            .nodes
+            .get_mut(node)
+            .ok_or(NetworkError::NodeNotFound(node))?;
+        if node_ref.is_up() == up {
+            return Ok(());
+        }
+        node_ref.set_up(up);
+        if up {
+            node_ref.set_routing_protocol(self.routing.create_protocol());
+        }
+        self.hop_trees.clear();
+        self.record(RecordedEvent::NodeState { node, up });
+        if !up {
+            let mut lost = vec![];
+            self.queues.retain(|(owner, _), queue| {
+                if *owner != node {
+                    return true;
+                }
+                lost.extend(queue.clear().into_iter().map(|packet| packet.uuid));
+                false
+            });
+            for uuid in lost {
+                self.drop_packet(uuid, Some(node), DropReason::NodeDown);
+            }
+        }
+
+        match self.routing {
+            Routing::Global => self.calculate_routes(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes a node along with its ethers; the slab key may be reused by later nodes.
+    pub fn remove_node(&mut self, node: NodeId) -> Result<(), NetworkError> {
+        self.get_node(node)?;
+        let ethers: Vec<EtherId> = self
+            .ethers
+            .iter()
+            .filter(|(_, ether)| {
+                ether
+                    .get_interfaces()
+                    .iter()
+                    .any(|interface| interface.get_to_owner().0 == node)
+            })
+            .map(|(id, _)| id)
+            .collect();
+        for ether in ethers {
+            self.drop_traffic(ether, DropReason::NodeRemoved);
+            self.loads.remove(&ether);
+            self.record(RecordedEvent::EtherRemoved { ether });
+            for interface in self.ethers.remove(ether).get_interfaces() {
+                let (owner, owner_interface) = interface.get_to_owner();
+                if let Some(interface) = self
+                    .nodes
+                    .get(owner)
+                    .and_then(|owner| owner.get_interface(owner_interface))
+                {
+                    interface.disconnect();
+                }
+            }
+        }
+
+        let mut lost = vec![];
+        self.incoming.retain(|(receiver, _, packet)| {
+            if *receiver != node {
+                return true;
+            }
+            lost.push(packet.uuid);
+            false
+        });
+        for uuid in lost {
+            self.drop_packet(uuid, Some(node), DropReason::NodeRemoved);
+        }
+        self.nodes.remove(node);
+        for (_, other) in self.nodes.iter_mut() {
+            other.forget_routes(node);
+        }
+        self.hop_trees.clear();
+        self.record(RecordedEvent::NodeRemoved { node });
+
+        match self.routing {
+            Routing::Global => self.calculate_routes(),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn send(
+        &mut self,
+        uuid: Uuid,
+        source: NodeId,
+        destination: NodeId,
+        size: u64,
+        ttl: u8,
+    ) -> Result<(), NetworkError> {
+        self.get_node(source)?;
+        self.get_node(destination)?;
+        self.track_packet(uuid, source, destination, size)?;
+        self.emit(Event::Send {
+            uuid,
+            source,
+            destination,
+            size,
+            ttl,
+        });
         self.incoming.push((
             source,
-            "localhost".to_string(),
+            LOCALHOST.to_string(),
             Packet {
                 uuid,
                 source,
                 current_sender: source,
                 destination,
+                payload: Payload::Data,
+                size,
+                ttl,
+                path: vec![],
             },
-        ))
-        /*let node = self.nodes.get(node).expect("Node not found!");
-        self.outgoing.push((
-            node.get_interface(_interface)
-                .get_connected_ether()
-                .expect("Interface not connected!"),
-            node.get_position(),
-            packet,
-        ))*/
+        ));
+        Ok(())
+    }
+
+    pub fn generate_uuid(&mut self) -> Uuid {
+        random_uuid(&mut self.rng)
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Switches every node to the given routing; with `Routing::Global` the routes are
+    /// calculated right away.
+    pub fn set_routing(&mut self, routing: Routing) -> Result<(), NetworkError> {
+        for (_, node) in self.nodes.iter_mut() {
+            node.set_routing_protocol(routing.create_protocol());
+        }
+        self.routing = routing;
+        match self.routing {
+            Routing::Global => self.calculate_routes(),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_routing(&self) -> &Routing {
+        &self.routing
+    }
+
+    pub fn schedule_send(
+        &mut self,
+        time: SimTime,
+        uuid: Uuid,
+        source: NodeId,
+        destination: NodeId,
+        size: u64,
+        ttl: u8,
+    ) -> Result<(), NetworkError> {
+        self.schedule(
+            time,
+            ScheduledAction::Send {
+                uuid,
+                source,
+                destination,
+                size,
+                ttl,
+            },
+        )
+    }
+
+    pub fn schedule(&mut self, time: SimTime, action: ScheduledAction) -> Result<(), NetworkError> {
+        match &action {
+            ScheduledAction::Send {
+                source,
+                destination,
+                ..
+            } => {
+                self.get_node(*source)?;
+                self.get_node(*destination)?;
+            }
+            ScheduledAction::LinkDown(ether)
+            | ScheduledAction::LinkUp(ether)
+            | ScheduledAction::Impair(ether, _) => {
+                self.is_link_up(*ether)?;
+            }
+            ScheduledAction::NodeFail(node)
+            | ScheduledAction::NodeRestart(node)
+            | ScheduledAction::NodeRemove(node) => {
+                self.get_node(*node)?;
+            }
+        }
+        let index = self
+            .scheduled
+            .iter()
+            .position(|(scheduled_time, _)| *scheduled_time > time)
+            .unwrap_or(self.scheduled.len());
+        self.scheduled.insert(index, (time, action));
+        Ok(())
     }
 
     pub fn get_packets_count(&self) -> usize {
-        self.transmissions.len()
+        self.transmissions
+            .iter()
+            .filter(|transmission| transmission.packet.is_data())
+            .count()
     }
 
-    /*pub fn receive(&mut self, node: NodeId, interface: NodeInterfaceId, packet: Packet) {
-        self.incoming.push((node, interface, packet))
-    }*/
+    pub fn get_scheduled_count(&self) -> usize {
+        self.scheduled.len()
+    }
 
-    fn calculate_preferred_path(
-        &self,
+    pub fn set_queue_settings(&mut self, settings: QueueSettings) {
+        self.queue_settings = settings;
+    }
+
+    pub fn get_queue_settings(&self) -> &QueueSettings {
+        &self.queue_settings
+    }
+
+    /// Non-empty output queues by sending node and interface.
+    pub fn queues(&self) -> impl Iterator<Item = (NodeId, &NodeInterfaceId, &OutputQueue)> {
+        self.queues
+            .iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .map(|((node, interface), queue)| (*node, interface, queue))
+    }
+
+    pub fn get_load(&self, ether: EtherId) -> u64 {
+        self.loads.get(&ether).copied().unwrap_or(0)
+    }
+
+    /// Share of the last `UTILIZATION_WINDOW` the ether spent serializing packets, in its
+    /// busiest direction. Always 0 for ethers without a bandwidth.
+    pub fn get_utilization(&self, ether: EtherId) -> f64 {
+        let from = self.time.saturating_sub(UTILIZATION_WINDOW);
+        self.busy
+            .iter()
+            .filter(|((busy_ether, _), _)| *busy_ether == ether)
+            .map(|(_, busy)| busy.within(from, self.time))
+            .max()
+            .map_or(0.0, |busy| busy as f64 / UTILIZATION_WINDOW as f64)
+    }
+
+    pub fn get_time(&self) -> SimTime {
+        self.time
+    }
+
+    /// Whether all data packets have been delivered or dropped; routing protocol chatter
+    /// never stops, so it doesn't count.
+    pub fn is_idle(&self) -> bool {
+        self.scheduled.is_empty()
+            && self.get_packets_count() == 0
+            && self.incoming.iter().all(|(.., packet)| !packet.is_data())
+            && self
+                .queues
+                .values()
+                .all(|queue| queue.packets().all(|packet| !packet.is_data()))
+    }
+
+    pub fn run_until_idle(&mut self, time_limit: Option<SimTime>) -> Result<(), NetworkError> {
+        while !self.is_idle() && time_limit.is_none_or(|limit| self.time < limit) {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn report(&self) -> Report {
+        Report::new(
+            self.seed,
+            self.time,
+            self.packets.values().cloned().collect(),
+            self.flow_statistics(),
+        )
+    }
+
+    /// Statistics of every (source, destination) pair data packets were sent between.
+    pub fn flow_statistics(&self) -> Vec<FlowStatistics> {
+        let mut flows: BTreeMap<(NodeId, NodeId), Vec<&PacketRecord>> = BTreeMap::new();
+        for packet in self.packets.values() {
+            flows
+                .entry((packet.source, packet.destination))
+                .or_default()
+                .push(packet);
+        }
+        flows
+            .into_iter()
+            .map(|((source, destination), packets)| {
+                FlowStatistics::new(source, destination, &packets)
+            })
+            .collect()
+    }
+
+    /// Changes whenever a data packet is sent or comes to an end, so that what is derived
+    /// from the packets, like `flow_statistics`, can be kept until then.
+    pub fn get_packet_updates(&self) -> u64 {
+        self.packet_updates
+    }
+
+    /// Starts the record of a data packet sent now.
+    fn track_packet(
+        &mut self,
+        uuid: Uuid,
         source: NodeId,
         destination: NodeId,
-        distances: &Vec<(NodeId, NodeId, i64)>,
-    ) -> Option<Vec<NodeId>> {
-        if source == destination {
-            return Some(vec![source, destination]);
-        }
-
-        let mut visited = HashMap::new();
-        let mut shortest: IndexMap<NodeId, (NodeId, i64)> = IndexMap::new();
-        shortest.insert(source, (source, 0));
-
-        while !shortest.is_empty() {
-            let current_id = shortest.first().unwrap().0.clone();
-            let (previous_id, current_distance) = shortest.remove(&current_id).unwrap();
-            if current_id != destination {
-                for (from, to, distance) in distances.iter() {
-                    if *from == current_id {
-                        let distance_to_next = current_distance + distance;
-                        if !visited.contains_key(to)
-                            && if let Some((_, known_distance_to_next)) = shortest.get(to) {
-                                distance_to_next < *known_distance_to_next
-                            } else {
-                                true
-                            }
-                        {
-                            shortest.insert(*to, (current_id, distance_to_next));
-                        }
-                    }
+        size: u64,
+    ) -> Result<(), NetworkError> {
+        let mut record = PacketRecord::new(uuid, source, destination, size, self.time);
+        record.shortest_hops = self.shortest_hops(source, destination)?;
+        self.packets.insert(uuid, record);
+        self.packet_updates += 1;
+        Ok(())
+    }
+
+    /// Hops on the shortest path between two nodes over the links and nodes that are up.
+    fn shortest_hops(
+        &mut self,
+        source: NodeId,
+        destination: NodeId,
+    ) -> Result<Option<u32>, NetworkError> {
+        if !self.hop_trees.contains_key(&source) {
+            let routing_graph = self.routing_graph()?;
+            let mut graph = Graph::new();
+            for node in routing_graph.nodes() {
+                graph.add_node(node);
+                for (neighbor, _) in routing_graph.neighbors(node) {
+                    graph.add_edge(node, *neighbor, 1);
                 }
+            }
+            self.hop_trees
+                .insert(source, graph.shortest_path_tree(source));
+        }
+        Ok(self.hop_trees[&source]
+            .distance(destination)
+            .map(|hops| hops as u32))
+    }
+
+    /// Records every event about data packets from now on.
+    pub fn set_event_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.event_sink = Some(sink);
+    }
+
+    /// Queues an event for the sink, if there is one.
+    fn emit(&mut self, event: Event) {
+        if self.event_sink.is_some() {
+            self.events.push((self.time, event));
+        }
+    }
+
+    /// Records everything drawn from now on, so that it can be replayed.
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
 
-                shortest.sort_by(|_, a, _, b| a.1.cmp(&b.1));
+    /// Writes every packet put on a link from now on to that link's capture file.
+    pub fn set_capture(&mut self, capture: Capture) {
+        self.capture = Some(capture);
+    }
+
+    fn record(&mut self, event: RecordedEvent) {
+        if self.recorder.is_some() {
+            self.recorded.push((self.time, event));
+        }
+    }
+
+    fn recording_header(&self) -> RecordingHeader {
+        RecordingHeader {
+            seed: self.seed,
+            time: self.time,
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(id, node)| RecordedNode {
+                    id,
+                    kind: node.get_kind(),
+                    position: node.get_position(),
+                    autonomous_system: node.get_autonomous_system(),
+                    up: node.is_up(),
+                })
+                .collect(),
+            ethers: self
+                .ethers
+                .iter()
+                .map(|(id, ether)| RecordedEther {
+                    id,
+                    segments: ether.get_segments(),
+                    up: ether.is_up(),
+                })
+                .collect(),
+        }
+    }
+
+    fn flush_events(&mut self) -> Result<(), NetworkError> {
+        if let Some(sink) = self.event_sink.as_mut() {
+            for (time, event) in self.events.drain(..) {
+                sink.record(time, &event)
+                    .map_err(|err| NetworkError::EventSink(err.to_string()))?;
             }
+        }
+        let header = match &self.recorder {
+            Some(recorder) if !recorder.is_started() => Some(self.recording_header()),
+            _ => None,
+        };
+        if let Some(recorder) = self.recorder.as_mut() {
+            let result = header.map_or(Ok(()), |header| recorder.start(&header));
+            result
+                .and_then(|_| {
+                    self.recorded
+                        .drain(..)
+                        .try_for_each(|(time, event)| recorder.record(time, &event))
+                })
+                .map_err(|err| NetworkError::Recording(err.to_string()))?;
+        }
+        Ok(())
+    }
 
-            visited.insert(current_id, previous_id);
+    /// Loses a packet for good, unless a copy of it is left; `node` is where it was, if it
+    /// wasn't on a link.
+    fn drop_packet(&mut self, uuid: Uuid, node: Option<NodeId>, reason: DropReason) {
+        let others = self.end_copy(uuid);
+        match self.packets.get(&uuid) {
+            Some(record) if others || record.status != PacketStatus::InFlight => {
+                self.emit(Event::Duplicate {
+                    uuid,
+                    node,
+                    reason: Some(reason),
+                });
+                return;
+            }
+            Some(_) => self.emit(Event::Drop { uuid, node, reason }),
+            None => return,
         }
+        let status = match reason {
+            DropReason::Corrupted => PacketStatus::Corrupted,
+            DropReason::Expired => PacketStatus::Expired,
+            _ => PacketStatus::Dropped,
+        };
+        self.finish_packet(uuid, status);
+    }
 
-        let mut path = vec![];
-        let mut current = destination;
-        while current != source {
-            path.push(current);
-            if let Some(previous) = visited.get(&current) {
-                current = *previous;
-            } else {
-                return None;
+    /// Accounts for a copy of a packet coming to an end, and tells whether others are left.
+    fn end_copy(&mut self, uuid: Uuid) -> bool {
+        match self.copies.get_mut(&uuid) {
+            Some(extra) => {
+                *extra -= 1;
+                if *extra == 0 {
+                    self.copies.remove(&uuid);
+                }
+                true
             }
+            None => false,
         }
-        path.push(source);
-        path.reverse();
-        Some(path)
     }
 
-    pub fn calculate_routes(&mut self) {
-        let mut distances = vec![];
+    fn finish_packet(&mut self, uuid: Uuid, status: PacketStatus) {
+        if let Some(record) = self.packets.get_mut(&uuid) {
+            if record.status == PacketStatus::InFlight {
+                record.finish(status, self.time);
+                self.packet_updates += 1;
+            }
+        }
+    }
+
+    /*pub fn receive(&mut self, node: NodeId, interface: NodeInterfaceId, packet: Packet) {
+        self.incoming.push((node, interface, packet))
+    }*/
+
+    pub fn routing_graph(&self) -> Result<Graph, NetworkError> {
+        let mut graph = Graph::new();
+        for (id, _) in self.nodes.iter() {
+            graph.add_node(id);
+        }
+        for (_, ether) in self.ethers.iter().filter(|(_, ether)| ether.is_up()) {
+            for (from, to) in ether.get_directions() {
+                let (from_node, to_node) = (self.get_node(from)?, self.get_node(to)?);
+                if !from_node.is_up() || !to_node.is_up() {
+                    continue;
+                }
+                let (from_position, to_position) =
+                    (from_node.get_position(), to_node.get_position());
+                let cost = if LATENCY_COST.load(Ordering::Relaxed) {
+                    ether.get_attributes().transmission_time(
+                        from_position,
+                        to_position,
+                        PACKET_SIZE,
+                    ) as Cost
+                } else {
+                    distance_between((to_position, from_position)) as Cost
+                };
+                graph.add_edge(from, to, cost);
+            }
+        }
+        Ok(graph)
+    }
+
+    pub fn calculate_routes(&mut self) -> Result<(), NetworkError> {
+        let graph = self.routing_graph()?;
+
+        let mut neighbor_interfaces: HashMap<(NodeId, NodeId), NodeInterfaceId> = HashMap::new();
         for (_, ether) in self.ethers.iter() {
-            for (from, to, multiplier) in ether.get_distance_multipliers() {
-                let distance = distance_between((
-                    self.nodes.get(to).unwrap().get_position(),
-                    self.nodes.get(from).unwrap().get_position(),
-                ));
-                distances.push((from, to, (distance * multiplier) as i64));
+            let interfaces = ether.get_interfaces();
+            for interface in interfaces.iter() {
+                let (owner, owner_interface) = interface.get_to_owner();
+                for peer in interfaces.iter() {
+                    let (peer_owner, _) = peer.get_to_owner();
+                    if peer_owner != owner {
+                        neighbor_interfaces.insert((owner, peer_owner), owner_interface.clone());
+                    }
+                }
             }
         }
 
         let mut routes = vec![];
 
-        for (_, source) in self.nodes.iter() {
-            for (_, destination) in self.nodes.iter() {
-                let (source_id, destination_id) = (source.get_id(), destination.get_id());
-                let path = self
-                    .calculate_preferred_path(source_id, destination_id, &distances)
-                    .expect("Cannot calculate path: unreachable!");
-                routes.push((source_id, destination_id, path[1]));
+        for (source, _) in self.nodes.iter() {
+            let tree = graph.shortest_path_tree(source);
+            for (destination, _) in self.nodes.iter() {
+                if destination == source {
+                    continue;
+                }
+                let interfaces: Vec<NodeInterfaceId> = tree
+                    .first_hops(destination)
+                    .iter()
+                    .filter_map(|first_hop| neighbor_interfaces.get(&(source, *first_hop)))
+                    .cloned()
+                    .collect();
+                routes.push((source, destination, interfaces));
             }
         }
 
-        for (source, destination, send_to) in routes.drain(..) {
+        // Unreachable destinations get an empty route, so their packets are dropped.
+        for (source, destination, interfaces) in routes.drain(..) {
             self.nodes
                 .get_mut(source)
-                .unwrap()
-                .set_known_route(destination, send_to);
+                .ok_or(NetworkError::NodeNotFound(source))?
+                .set_known_route(destination, interfaces);
         }
+        Ok(())
     }
 
-    pub fn render(&mut self, renderer: &mut Renderer) -> Result<(), String> {
-        renderer.canvas.set_draw_color(Color::BLACK);
-        for (_, ether) in self.ethers.iter() {
-            ether.draw(renderer)?;
+    pub fn nodes(&self) -> impl Iterator<Item = &dyn Node> {
+        self.nodes.iter().map(|(_, node)| node.as_ref())
+    }
+
+    pub fn ethers(&self) -> impl Iterator<Item = &dyn Ether> {
+        self.ethers.iter().map(|(_, ether)| ether.as_ref())
+    }
+
+    pub fn transmissions(&self) -> &[Transmission] {
+        &self.transmissions
+    }
+
+    fn dispatch(
+        &mut self,
+        node: NodeId,
+        sent: Vec<(NodeInterfaceId, Packet)>,
+    ) -> Result<(), NetworkError> {
+        for (_, outgoing_packet) in sent.iter().filter(|(_, packet)| packet.is_data()) {
+            if !self.packets.contains_key(&outgoing_packet.uuid) {
+                self.track_packet(
+                    outgoing_packet.uuid,
+                    outgoing_packet.source,
+                    outgoing_packet.destination,
+                    outgoing_packet.size,
+                )?;
+            }
+        }
+        let node = self
+            .nodes
+            .get(node)
+            .ok_or(NetworkError::NodeNotFound(node))?;
+        let (mut events, mut dropped) = (vec![], vec![]);
+        for (outgoing_interface, outgoing_packet) in sent {
+            let ether = match node
+                .get_interface(outgoing_interface.clone())
+                .ok_or_else(|| {
+                    NetworkError::InterfaceNotFound(node.get_id(), outgoing_interface.clone())
+                })?
+                .get_connected_ether()
+            {
+                Some(ether) => ether,
+                // The link behind the interface was removed along with its other end.
+                None => {
+                    dropped.push((outgoing_packet.uuid, DropReason::NoRoute));
+                    continue;
+                }
+            };
+            let settings = self
+                .ethers
+                .get(ether)
+                .ok_or(NetworkError::EtherNotFound(ether))?
+                .get_attributes()
+                .queue
+                .as_ref()
+                .unwrap_or(&self.queue_settings);
+            let queue = self
+                .queues
+                .entry((node.get_id(), outgoing_interface.clone()))
+                .or_insert_with(|| OutputQueue::new(ether, node.get_position()));
+            let (uuid, is_data) = (outgoing_packet.uuid, outgoing_packet.is_data());
+            match queue.enqueue(outgoing_packet, settings, &mut self.rng) {
+                Ok(()) if is_data => events.push(Event::Enqueue {
+                    uuid,
+                    node: node.get_id(),
+                    interface: outgoing_interface,
+                    depth: queue.len(),
+                }),
+                Ok(()) => {}
+                Err(_) if queue.len() >= settings.capacity => {
+                    dropped.push((uuid, DropReason::QueueFull))
+                }
+                Err(_) => dropped.push((uuid, DropReason::EarlyDrop)),
+            }
+        }
+        let node = node.get_id();
+        for event in events {
+            self.emit(event);
+        }
+        for (uuid, reason) in dropped {
+            self.drop_packet(uuid, Some(node), reason);
         }
+        Ok(())
+    }
 
-        for (_, node) in self.nodes.iter() {
-            node.draw(renderer)?;
+    pub fn advance(&mut self, duration: SimTime) -> Result<(), NetworkError> {
+        let until = self.time + duration;
+        while self.time < until {
+            self.step()?;
         }
+        Ok(())
+    }
+
+    pub fn step(&mut self) -> Result<(), NetworkError> {
+        self.time += TICK;
 
-        for transmission in self.transmissions.iter_mut() {
-            transmission.travelled += 1;
-            let c =
-                (100f64 * (transmission.travelled as f64) / (transmission.distance as f64)) as i32;
-            transmission.packet.draw(
-                renderer,
-                (transmission.from * (100 - c) + transmission.to * c) / 100,
-            )?;
-            if transmission.travelled >= transmission.distance {
+        while let Some((time, ..)) = self.scheduled.front() {
+            if *time > self.time {
+                break;
+            }
+            match self.scheduled.pop_front() {
+                Some((
+                    _,
+                    ScheduledAction::Send {
+                        uuid,
+                        source,
+                        destination,
+                        size,
+                        ttl,
+                    },
+                )) => {
+                    if self.nodes.contains(source) && self.nodes.contains(destination) {
+                        self.send(uuid, source, destination, size, ttl)?;
+                    } else {
+                        // One of the ends was removed in the meantime.
+                        self.track_packet(uuid, source, destination, size)?;
+                        self.drop_packet(uuid, None, DropReason::NodeRemoved);
+                    }
+                }
+                // Whatever the event was about may have been removed in the meantime.
+                Some((
+                    _,
+                    ScheduledAction::LinkDown(ether)
+                    | ScheduledAction::LinkUp(ether)
+                    | ScheduledAction::Impair(ether, _),
+                )) if !self.ethers.contains(ether) => {}
+                Some((
+                    _,
+                    ScheduledAction::NodeFail(node)
+                    | ScheduledAction::NodeRestart(node)
+                    | ScheduledAction::NodeRemove(node),
+                )) if !self.nodes.contains(node) => {}
+                Some((_, ScheduledAction::LinkDown(ether))) => self.set_link_up(ether, false)?,
+                Some((_, ScheduledAction::LinkUp(ether))) => self.set_link_up(ether, true)?,
+                Some((_, ScheduledAction::NodeFail(node))) => self.set_node_up(node, false)?,
+                Some((_, ScheduledAction::NodeRestart(node))) => self.set_node_up(node, true)?,
+                Some((_, ScheduledAction::NodeRemove(node))) => self.remove_node(node)?,
+                Some((_, ScheduledAction::Impair(ether, impairments))) => {
+                    self.set_impairments(ether, impairments)?
+                }
+                None => {}
+            }
+        }
+
+        let mut ticked = vec![];
+        for (id, node) in self.nodes.iter_mut().filter(|(_, node)| node.is_up()) {
+            ticked.push((id, node.tick(self.time)));
+        }
+        for (node, sent) in ticked {
+            self.dispatch(node, sent)?;
+        }
+
+        let (mut damaged, mut received, mut ended) = (vec![], vec![], vec![]);
+        for transmission in self.transmissions.iter() {
+            if transmission.is_arrived(self.time) {
+                ended.push(RecordedEvent::TransmissionEnd {
+                    id: transmission.id,
+                });
                 let (owner, owner_interface) = transmission.recipient.get_to_owner();
+                match transmission.impairment {
+                    Some(Impairment::Lost) => {
+                        damaged.push((transmission.packet.uuid, None, DropReason::Lost));
+                        continue;
+                    }
+                    // The receiver notices the bad checksum and discards the packet.
+                    Some(Impairment::Corrupted) => {
+                        damaged.push((
+                            transmission.packet.uuid,
+                            Some(owner),
+                            DropReason::Corrupted,
+                        ));
+                        continue;
+                    }
+                    Some(Impairment::Duplicate) | None => {}
+                }
                 self.incoming
                     .push((owner, owner_interface.clone(), transmission.packet.clone()));
-                println!(
-                    "{:6} # {} {:3} > {:3} : RX {:3} | {}",
-                    renderer.timer_subsystem.ticks(),
-                    transmission.packet.uuid,
-                    transmission.packet.source,
-                    transmission.packet.destination,
-                    owner,
-                    owner_interface,
-                );
+                if transmission.packet.is_data() {
+                    received.push(Event::Receive {
+                        uuid: transmission.packet.uuid,
+                        node: owner,
+                        interface: owner_interface,
+                    });
+                }
             }
         }
+        for event in received {
+            self.emit(event);
+        }
+        for event in ended {
+            self.record(event);
+        }
 
+        let time = self.time;
         self.transmissions
-            .retain(|transmission| transmission.travelled < transmission.distance);
-
-        for (node, interface, packet) in self.incoming.drain(..) {
-            let node = self.nodes.get_mut(node).expect("Node not found!");
-            self.outgoing
-                .extend(node.receive(interface, packet).drain(..).map(
-                    |(outgoing_interface, outgoing_packet)| {
-                        (
-                            node.get_interface(outgoing_interface.clone())
-                                .get_connected_ether()
-                                .expect("Interface not connected!"),
-                            node.get_position(),
-                            outgoing_packet,
-                        )
-                    },
-                ));
+            .retain(|transmission| !transmission.is_arrived(time));
+        for (uuid, node, reason) in damaged {
+            self.drop_packet(uuid, node, reason);
+        }
+
+        for (node, interface, mut packet) in std::mem::take(&mut self.incoming) {
+            let (uuid, destination) = (packet.uuid, packet.destination);
+            if packet.is_data() {
+                packet.path.push(Hop {
+                    node,
+                    interface: interface.clone(),
+                    time: self.time,
+                });
+                // Copies arriving after the packet's fate was decided don't change its record.
+                if let Some(record) = self
+                    .packets
+                    .get_mut(&uuid)
+                    .filter(|record| record.status == PacketStatus::InFlight)
+                {
+                    record.hops = packet
+                        .path
+                        .iter()
+                        .filter(|hop| hop.interface != LOCALHOST)
+                        .count() as u32;
+                    record.path = packet.path.clone();
+                }
+            }
+            let expiring = packet.ttl <= 1 && packet.current_sender != node;
+            let receiver = self
+                .nodes
+                .get_mut(node)
+                .ok_or(NetworkError::NodeNotFound(node))?;
+            if !receiver.is_up() {
+                self.drop_packet(uuid, Some(node), DropReason::NodeDown);
+                continue;
+            }
+            let tracked = self.packets.contains_key(&uuid);
+            let sent = receiver.receive(interface, packet, self.time, &mut self.rng);
+            let forwarded = sent.iter().find(|(_, sent)| sent.uuid == uuid);
+            if destination == node {
+                self.end_copy(uuid);
+                match self.packets.get(&uuid).map(|record| record.status) {
+                    Some(PacketStatus::InFlight) => {
+                        self.emit(Event::Deliver { uuid, node });
+                        self.finish_packet(uuid, PacketStatus::Delivered);
+                    }
+                    Some(_) => self.emit(Event::Duplicate {
+                        uuid,
+                        node: Some(node),
+                        reason: None,
+                    }),
+                    None => {}
+                }
+            } else if let Some((interface, forwarded)) = forwarded {
+                if tracked {
+                    self.emit(Event::Forward {
+                        uuid,
+                        node,
+                        interface: interface.clone(),
+                        ttl: forwarded.ttl,
+                    });
+                }
+            } else {
+                let reason = if expiring {
+                    DropReason::Expired
+                } else {
+                    DropReason::NoRoute
+                };
+                self.drop_packet(uuid, Some(node), reason);
+            }
+            self.dispatch(node, sent)?;
         }
 
-        for (ether, from_position, packet) in self.outgoing.drain(..) {
-            for interface in self.ethers.get(ether).unwrap().get_interfaces() {
+        for (sender, queue) in self.queues.iter_mut() {
+            let attributes = self
+                .ethers
+                .get(queue.get_ether())
+                .map(|ether| ether.get_attributes());
+            while let Some((from_position, packet)) = queue.dequeue(time) {
+                // Without a bandwidth a link takes packets as fast as they come.
+                let serialized = attributes
+                    .is_none_or(|attributes| attributes.serialization_delay(packet.size) > 0);
+                self.outgoing
+                    .push((sender.clone(), queue.get_ether(), from_position, packet));
+                if serialized {
+                    break;
+                }
+            }
+        }
+
+        let (mut lost, mut started, mut recorded) = (vec![], vec![], vec![]);
+        for (sender, ether_id, from_position, packet) in std::mem::take(&mut self.outgoing) {
+            let ether = self
+                .ethers
+                .get(ether_id)
+                .ok_or(NetworkError::EtherNotFound(ether_id))?;
+            let attributes = ether.get_attributes();
+            for interface in ether.get_interfaces() {
                 let (owner, owner_interface) = interface.get_to_owner();
-                let owner = self.nodes.get(owner).unwrap();
+                let owner = self
+                    .nodes
+                    .get(owner)
+                    .ok_or(NetworkError::NodeNotFound(owner))?;
                 if owner.will_receive(owner_interface, &packet) {
-                    self.transmissions.push(Transmission::new(
+                    if !ether.is_up() {
+                        lost.push((packet.uuid, sender.0));
+                        continue;
+                    }
+                    if packet.is_data() {
+                        *self.loads.entry(ether_id).or_insert(0) += 1;
+                    }
+                    if let Some(capture) = self.capture.as_mut() {
+                        capture
+                            .capture(ether_id, self.time, sender.0, owner.get_id(), &packet)
+                            .map_err(|err| NetworkError::Capture(err.to_string()))?;
+                    }
+                    let impairments = &attributes.impairments;
+                    let duration = attributes.transmission_time(
                         from_position,
                         owner.get_position(),
-                        interface,
-                        packet.clone(),
-                    ));
+                        packet.size,
+                    );
+                    let copies = if chance(&mut self.rng, impairments.duplication) {
+                        2
+                    } else {
+                        1
+                    };
+                    if copies > 1 && self.packets.contains_key(&packet.uuid) {
+                        *self.copies.entry(packet.uuid).or_insert(0) += copies - 1;
+                    }
+                    for copy in 0..copies {
+                        let jitter = match impairments.jitter {
+                            0 => 0,
+                            jitter => self.rng.gen_range(0..=jitter),
+                        };
+                        let impairment = if chance(&mut self.rng, impairments.loss) {
+                            Some(Impairment::Lost)
+                        } else if chance(&mut self.rng, impairments.corruption) {
+                            Some(Impairment::Corrupted)
+                        } else if copy > 0 {
+                            Some(Impairment::Duplicate)
+                        } else {
+                            None
+                        };
+                        if packet.is_data() {
+                            started.push(Event::TransmitStart {
+                                uuid: packet.uuid,
+                                node: sender.0,
+                                interface: sender.1.clone(),
+                                ether: ether_id,
+                                recipient: owner.get_id(),
+                                duration: duration + jitter,
+                            });
+                        }
+                        let id = self.next_transmission;
+                        self.next_transmission += 1;
+                        recorded.push(RecordedEvent::TransmissionStart(RecordedTransmission {
+                            id,
+                            ether: ether_id,
+                            from: from_position,
+                            to: owner.get_position(),
+                            duration: (duration + jitter).max(TICK),
+                            kind: packet.payload.kind(),
+                            impairment,
+                            load: self.loads.get(&ether_id).copied().unwrap_or(0),
+                        }));
+                        self.transmissions.push(Transmission {
+                            id,
+                            impairment,
+                            ..Transmission::new(
+                                ether_id,
+                                self.time,
+                                duration + jitter,
+                                from_position,
+                                owner.get_position(),
+                                interface.clone(),
+                                packet.clone(),
+                            )
+                        });
+                    }
                 }
             }
+            // The interface puts a single packet at a time on the link; the next one has to
+            // wait until this one is serialized.
+            let serialization_delay = attributes.serialization_delay(packet.size);
+            if let Some(queue) = self.queues.get_mut(&sender) {
+                queue.set_busy_until(self.time + serialization_delay);
+            }
+            if ether.is_up() && serialization_delay > 0 {
+                self.busy
+                    .entry((ether_id, sender.0))
+                    .or_default()
+                    .add(self.time, self.time + serialization_delay);
+            }
         }
 
-        Ok(())
+        let window_start = self.time.saturating_sub(UTILIZATION_WINDOW);
+        self.busy.retain(|_, busy| busy.expire(window_start));
+
+        for event in started {
+            self.emit(event);
+        }
+        for event in recorded {
+            self.record(event);
+        }
+        for (uuid, node) in lost {
+            self.drop_packet(uuid, Some(node), DropReason::LinkDown);
+        }
+
+        self.flush_events()
     }
 }
 
-struct Transmission {
+/// What happened to a packet on its way, drawn on it until it arrives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Impairment {
+    /// The packet never arrives.
+    Lost,
+    /// The packet arrives damaged and is discarded.
+    Corrupted,
+    /// An extra copy of a packet.
+    Duplicate,
+}
+
+fn chance(rng: &mut StdRng, probability: f64) -> bool {
+    probability > 0.0 && rng.gen_bool(probability)
+}
+
+pub struct Transmission {
+    id: u64,
+    ether: EtherId,
     from: Point,
     to: Point,
-    distance: i32,
-    travelled: i32,
+    started: SimTime,
+    duration: SimTime,
     recipient: EtherInterface,
     packet: Packet,
+    impairment: Option<Impairment>,
 }
 
 impl Transmission {
-    pub fn new(from: Point, to: Point, recipient: EtherInterface, packet: Packet) -> Transmission {
+    pub fn new(
+        ether: EtherId,
+        started: SimTime,
+        duration: SimTime,
+        from: Point,
+        to: Point,
+        recipient: EtherInterface,
+        packet: Packet,
+    ) -> Transmission {
         Transmission {
+            id: 0,
+            ether,
             from,
             to,
-            distance: distance_between((from, to)) as i32,
-            travelled: 0,
+            started,
+            duration: duration.max(TICK),
             recipient,
             packet,
+            impairment: None,
         }
     }
+
+    pub fn is_arrived(&self, time: SimTime) -> bool {
+        time >= self.started + self.duration
+    }
+
+    pub fn get_ether(&self) -> EtherId {
+        self.ether
+    }
+
+    pub fn get_packet(&self) -> &Packet {
+        &self.packet
+    }
+
+    pub fn get_impairment(&self) -> Option<Impairment> {
+        self.impairment
+    }
+
+    pub fn get_position(&self, time: SimTime) -> Point {
+        position_along((self.from, self.to), self.started, self.duration, time)
+    }
+}
+
+/// Intervals during which something was busy, oldest first and not overlapping.
+#[derive(Default)]
+struct BusyTime {
+    intervals: VecDeque<(SimTime, SimTime)>,
+    total: SimTime,
+}
+
+impl BusyTime {
+    fn add(&mut self, start: SimTime, end: SimTime) {
+        self.intervals.push_back((start, end));
+        self.total += end - start;
+    }
+
+    /// Forgets the intervals over by `time`, and tells whether any are left.
+    fn expire(&mut self, time: SimTime) -> bool {
+        while let Some((start, end)) = self.intervals.front().copied() {
+            if end > time {
+                break;
+            }
+            self.intervals.pop_front();
+            self.total -= end - start;
+        }
+        !self.intervals.is_empty()
+    }
+
+    /// How long it was busy between `from` and `to`, once the intervals over by `from` are
+    /// forgotten.
+    fn within(&self, from: SimTime, to: SimTime) -> SimTime {
+        let before = self
+            .intervals
+            .front()
+            .map_or(0, |(start, _)| from.saturating_sub(*start));
+        let after = self
+            .intervals
+            .back()
+            .map_or(0, |(_, end)| end.saturating_sub(to));
+        self.total.saturating_sub(before + after)
+    }
+}
+
+/// Where something travelling from one point to another between `started` and
+/// `started + duration` is at the given time.
+pub fn position_along(
+    (from, to): (Point, Point),
+    started: SimTime,
+    duration: SimTime,
+    time: SimTime,
+) -> Point {
+    let travelled = time.saturating_sub(started).min(duration);
+    let c = (100 * travelled / duration.max(1)) as i32;
+    Point::new(
+        (from.x() * (100 - c) + to.x() * c) / 100,
+        (from.y() * (100 - c) + to.y() * c) / 100,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Events(Rc<RefCell<Vec<Event>>>);
+
+    impl EventSink for Events {
+        fn record(&mut self, _: SimTime, event: &Event) -> io::Result<()> {
+            self.0.borrow_mut().push(event.clone());
+            Ok(())
+        }
+    }
+
+    /// Nodes 100 pixels apart in a line, endpoints at both ends, each link's interfaces
+    /// named `<node>-<peer>`.
+    fn chain(length: usize) -> (Network, Vec<NodeId>) {
+        let mut network = Network::new();
+        network.set_seed(1);
+        let nodes: Vec<NodeId> = (0..length)
+            .map(|index| {
+                let position = Point::new(100 * index as i32, 0);
+                if index == 0 || index == length - 1 {
+                    network.add_endpoint_node(position)
+                } else {
+                    network.add_router_node(position)
+                }
+            })
+            .collect();
+        for pair in nodes.windows(2) {
+            link(&mut network, pair[0], pair[1]);
+        }
+        network.calculate_routes().unwrap();
+        (network, nodes)
+    }
+
+    fn link(network: &mut Network, node1: NodeId, node2: NodeId) -> EtherId {
+        let interface1 = network
+            .add_router_interface(node1, format!("{}-{}", node1, node2))
+            .unwrap();
+        let interface2 = network
+            .add_router_interface(node2, format!("{}-{}", node2, node1))
+            .unwrap();
+        network
+            .connect_cable(
+                ((node1, interface1), (node2, interface2)),
+                LinkAttributes::default(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn unlimited_links_send_everything_queued_at_once() {
+        let (mut network, nodes) = chain(3);
+        let mut sent = vec![];
+        for time in 1..=50 {
+            for _ in 0..3 {
+                let uuid = network.generate_uuid();
+                network
+                    .schedule_send(time, uuid, nodes[0], nodes[2], PACKET_SIZE, 64)
+                    .unwrap();
+                sent.push(uuid);
+            }
+        }
+        network.advance(50).unwrap();
+        assert!(network.queues().next().is_none());
+        network.run_until_idle(Some(10_000)).unwrap();
+        assert!(sent.iter().all(|uuid| {
+            network.get_packet_record(*uuid).unwrap().status == PacketStatus::Delivered
+        }));
+    }
+
+    #[test]
+    fn limited_links_send_one_packet_at_a_time() {
+        let (mut network, nodes) = chain(2);
+        let mut attributes = network.ethers[0].get_attributes().clone();
+        attributes.bandwidth = Some(PACKET_SIZE as f64 / 10.0);
+        network.ethers[0].set_attributes(attributes);
+        for _ in 0..3 {
+            let uuid = network.generate_uuid();
+            network
+                .send(uuid, nodes[0], nodes[1], PACKET_SIZE, 64)
+                .unwrap();
+        }
+        network.step().unwrap();
+        assert_eq!(network.get_packets_count(), 1);
+        assert_eq!(
+            network.queues().next().map(|(.., queue)| queue.len()),
+            Some(2)
+        );
+        network.advance(10).unwrap();
+        assert_eq!(
+            network.queues().next().map(|(.., queue)| queue.len()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn stretch_compares_with_the_topology_packets_were_sent_in() {
+        // A line with a detour over a router above it.
+        let mut network = Network::new();
+        network.set_seed(1);
+        let source = network.add_endpoint_node(Point::new(0, 0));
+        let left = network.add_router_node(Point::new(100, 0));
+        let detour = network.add_router_node(Point::new(200, 100));
+        let right = network.add_router_node(Point::new(300, 0));
+        let destination = network.add_endpoint_node(Point::new(400, 0));
+        link(&mut network, source, left);
+        let shortcut = link(&mut network, left, right);
+        link(&mut network, left, detour);
+        link(&mut network, detour, right);
+        link(&mut network, right, destination);
+        network.calculate_routes().unwrap();
+
+        let send = |network: &mut Network| {
+            let uuid = network.generate_uuid();
+            network
+                .send(uuid, source, destination, PACKET_SIZE, 64)
+                .unwrap();
+            network.run_until_idle(Some(10_000)).unwrap();
+        };
+        send(&mut network);
+        network.set_link_up(shortcut, false).unwrap();
+        send(&mut network);
+
+        let flows = network.flow_statistics();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].delivered, 2);
+        assert_eq!(flows[0].mean_hops, Some(3.5));
+        assert_eq!(flows[0].shortest_hops, Some(3.5));
+        assert_eq!(flows[0].stretch, Some(1.0));
+    }
+
+    #[test]
+    fn utilization_is_the_share_of_the_window_spent_serializing() {
+        let (mut network, nodes) = chain(2);
+        assert_eq!(network.get_utilization(0), 0.0);
+        for _ in 0..3 {
+            let uuid = network.generate_uuid();
+            network
+                .send(uuid, nodes[0], nodes[1], PACKET_SIZE, 64)
+                .unwrap();
+        }
+        network.run_until_idle(Some(10_000)).unwrap();
+        // Links without a bandwidth are never busy.
+        assert_eq!(network.get_utilization(0), 0.0);
+
+        let mut attributes = network.ethers[0].get_attributes().clone();
+        attributes.bandwidth = Some(PACKET_SIZE as f64 / 10.0);
+        network.ethers[0].set_attributes(attributes);
+        for _ in 0..30 {
+            let uuid = network.generate_uuid();
+            network
+                .send(uuid, nodes[0], nodes[1], PACKET_SIZE, 64)
+                .unwrap();
+        }
+        // Each packet takes 10 ms to serialize, starting with this step.
+        network.step().unwrap();
+        network.advance(250).unwrap();
+        assert_eq!(network.get_utilization(0), 0.25);
+        network.advance(UTILIZATION_WINDOW - 250).unwrap();
+        assert_eq!(network.get_utilization(0), 0.3);
+        network.advance(250).unwrap();
+        assert_eq!(network.get_utilization(0), 0.05);
+        network.advance(UTILIZATION_WINDOW).unwrap();
+        assert_eq!(network.get_utilization(0), 0.0);
+    }
+
+    fn impair(network: &mut Network, ether: EtherId, impairments: Impairments) {
+        network.set_impairments(ether, impairments).unwrap();
+    }
+
+    #[test]
+    fn duplicates_are_delivered_once() {
+        let (mut network, nodes) = chain(3);
+        let events = Events::default();
+        network.set_event_sink(Box::new(events.clone()));
+        for ether in 0..2 {
+            let duplication = Impairments {
+                duplication: 1.0,
+                ..Impairments::default()
+            };
+            impair(&mut network, ether, duplication);
+        }
+        let uuid = network.generate_uuid();
+        network
+            .send(uuid, nodes[0], nodes[2], PACKET_SIZE, 64)
+            .unwrap();
+        network.run_until_idle(Some(10_000)).unwrap();
+
+        let record = network.get_packet_record(uuid).unwrap();
+        assert_eq!(record.status, PacketStatus::Delivered);
+        assert_eq!(record.hops, 2);
+        let events = events.0.borrow();
+        let count =
+            |matches: fn(&Event) -> bool| events.iter().filter(|event| matches(event)).count();
+        assert_eq!(count(|event| matches!(event, Event::Deliver { .. })), 1);
+        // The first link makes two copies, each of which the second one doubles.
+        assert_eq!(count(|event| matches!(event, Event::Duplicate { .. })), 3);
+    }
+
+    #[test]
+    fn lost_duplicates_leave_the_record_alone() {
+        let (mut network, nodes) = chain(2);
+        let events = Events::default();
+        network.set_event_sink(Box::new(events.clone()));
+        let impairments = Impairments {
+            loss: 0.5,
+            duplication: 1.0,
+            ..Impairments::default()
+        };
+        impair(&mut network, 0, impairments);
+        let mut sent = vec![];
+        for time in 1..=100 {
+            let uuid = network.generate_uuid();
+            network
+                .schedule_send(time, uuid, nodes[0], nodes[1], PACKET_SIZE, 64)
+                .unwrap();
+            sent.push(uuid);
+        }
+        network.run_until_idle(Some(10_000)).unwrap();
+
+        let events = events.0.borrow();
+        for uuid in sent {
+            let ends: Vec<&Event> = events
+                .iter()
+                .filter(|event| match event {
+                    Event::Deliver { uuid: end, .. } | Event::Drop { uuid: end, .. } => {
+                        *end == uuid
+                    }
+                    _ => false,
+                })
+                .collect();
+            assert_eq!(ends.len(), 1);
+            let record = network.get_packet_record(uuid).unwrap();
+            match ends[0] {
+                Event::Deliver { .. } => {
+                    assert_eq!(record.status, PacketStatus::Delivered);
+                    assert_eq!(record.hops, 1);
+                }
+                _ => {
+                    assert_eq!(record.status, PacketStatus::Dropped);
+                    assert_eq!(record.hops, 0);
+                }
+            }
+        }
+        assert!(network.copies.is_empty());
+    }
+
+    #[test]
+    fn removing_destination_drops_packets_in_flight() {
+        let (mut network, nodes) = chain(4);
+        let uuid = network.generate_uuid();
+        network
+            .schedule_send(1, uuid, nodes[0], nodes[3], PACKET_SIZE, 64)
+            .unwrap();
+        network
+            .schedule(150, ScheduledAction::NodeRemove(nodes[3]))
+            .unwrap();
+        network.run_until_idle(Some(10_000)).unwrap();
+        let record = network.get_packet_record(uuid).unwrap();
+        assert_eq!(record.status, PacketStatus::Dropped);
+        assert!(network.nodes[nodes[2]].get_routes().iter().all(|route| {
+            route.destination != nodes[3] && !route.interfaces.contains(&"2-3".to_string())
+        }));
+    }
+
+    #[test]
+    fn packets_for_unconnected_interfaces_are_dropped() {
+        let (mut network, nodes) = chain(4);
+        network.remove_node(nodes[3]).unwrap();
+        let uuid = network.generate_uuid();
+        network.packets.insert(
+            uuid,
+            PacketRecord::new(uuid, nodes[0], nodes[3], PACKET_SIZE, 0),
+        );
+        let packet = Packet {
+            uuid,
+            source: nodes[0],
+            current_sender: nodes[2],
+            destination: nodes[3],
+            payload: Payload::Data,
+            size: PACKET_SIZE,
+            ttl: 64,
+            path: vec![],
+        };
+        network
+            .dispatch(nodes[2], vec![("2-3".to_string(), packet)])
+            .unwrap();
+        assert_eq!(
+            network.get_packet_record(uuid).unwrap().status,
+            PacketStatus::Dropped
+        );
+    }
 }
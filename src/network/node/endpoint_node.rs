@@ -1,10 +1,11 @@
 use super::router_node::RouterNode;
-use crate::app::Renderer;
+use crate::network::error::NetworkError;
 use crate::network::ether::EtherId;
-use crate::network::node::{Node, NodeId, NodeInterface, NodeInterfaceId};
+use crate::network::node::{AsNumber, Node, NodeId, NodeInterface, NodeInterfaceId, NodeKind};
 use crate::network::packet::Packet;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::TextureQuery;
+use crate::network::routing::{Route, RoutingProtocol};
+use crate::network::{Point, SimTime};
+use rand::RngCore;
 
 pub struct EndpointNode(RouterNode);
 
@@ -13,6 +14,10 @@ impl Node for EndpointNode {
         self.0.get_id()
     }
 
+    fn get_kind(&self) -> NodeKind {
+        NodeKind::Endpoint
+    }
+
     fn get_position(&self) -> Point {
         self.0.get_position()
     }
@@ -21,21 +26,6 @@ impl Node for EndpointNode {
         self.0.corresponds_to_position(position)
     }
 
-    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
-        let position = self.get_position();
-        renderer.canvas.copy(
-            &renderer.endpoint_texture,
-            None,
-            Some(Rect::from_center(position, 50, 50)),
-        )?;
-        let TextureQuery { width, height, .. } = self.0.texture.query();
-        renderer.canvas.copy(
-            &self.0.texture,
-            None,
-            Some(Rect::from_center(position.offset(0, -10), width, height)),
-        )
-    }
-
     fn will_receive(&self, interface: NodeInterfaceId, packet: &Packet) -> bool {
         self.0.will_receive(interface, packet)
     }
@@ -44,35 +34,76 @@ impl Node for EndpointNode {
         &mut self,
         interface: NodeInterfaceId,
         packet: Packet,
+        time: SimTime,
+        rng: &mut dyn RngCore,
     ) -> Vec<(NodeInterfaceId, Packet)> {
-        self.0.receive(interface, packet)
+        self.0.receive(interface, packet, time, rng)
+    }
+
+    fn tick(&mut self, time: SimTime) -> Vec<(NodeInterfaceId, Packet)> {
+        self.0.tick(time)
+    }
+
+    fn get_known_route_interface(
+        &mut self,
+        source: NodeId,
+        destination: NodeId,
+        rng: &mut dyn RngCore,
+    ) -> Option<NodeInterfaceId> {
+        self.0.get_known_route_interface(source, destination, rng)
+    }
+
+    fn set_known_route(&mut self, destination: NodeId, interfaces: Vec<NodeInterfaceId>) {
+        self.0.set_known_route(destination, interfaces)
     }
 
-    fn get_known_route_interface(&self, destination: NodeId) -> NodeInterfaceId {
-        self.0.get_known_route_interface(destination)
+    fn forget_routes(&mut self, removed: NodeId) {
+        self.0.forget_routes(removed)
     }
 
-    fn set_known_route(&mut self, destination: NodeId, send_to: NodeId) {
-        self.0.set_known_route(destination, send_to)
+    fn get_routes(&self) -> Vec<Route> {
+        self.0.get_routes()
     }
 
-    fn get_interface(&self, interface: NodeInterfaceId) -> &NodeInterface {
+    fn set_routing_protocol(&mut self, protocol: Option<Box<dyn RoutingProtocol>>) {
+        self.0.set_routing_protocol(protocol)
+    }
+
+    fn is_up(&self) -> bool {
+        self.0.is_up()
+    }
+
+    fn set_up(&mut self, up: bool) {
+        self.0.set_up(up)
+    }
+
+    fn get_autonomous_system(&self) -> Option<AsNumber> {
+        self.0.get_autonomous_system()
+    }
+
+    fn set_autonomous_system(&mut self, autonomous_system: Option<AsNumber>) {
+        self.0.set_autonomous_system(autonomous_system)
+    }
+
+    fn get_interface(&self, interface: NodeInterfaceId) -> Option<&NodeInterface> {
         self.0.get_interface(interface)
     }
 
-    fn connect_interface(&self, interface: NodeInterfaceId, ether: EtherId) {
+    fn connect_interface(
+        &self,
+        interface: NodeInterfaceId,
+        ether: EtherId,
+    ) -> Result<(), NetworkError> {
         self.0.connect_interface(interface, ether)
     }
 
-    fn create_interface(&mut self, id: NodeInterfaceId) -> Result<NodeInterfaceId, String> {
+    fn create_interface(&mut self, id: NodeInterfaceId) -> Result<NodeInterfaceId, NetworkError> {
         self.0.create_interface(id)
     }
 }
 
 impl EndpointNode {
-    pub fn new(renderer: &mut Renderer, id: NodeId, position: Point) -> Self {
-        Self {
-            0: RouterNode::new(renderer, id, position),
-        }
+    pub fn new(id: NodeId, position: Point) -> Self {
+        Self(RouterNode::new(id, position))
     }
 }
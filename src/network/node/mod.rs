@@ -1,39 +1,78 @@
 pub mod endpoint_node;
 pub mod router_node;
 
+use super::error::NetworkError;
 use super::ether::EtherId;
 use super::packet::Packet;
-use crate::app::Renderer;
-use sdl2::rect::Point;
+use super::routing::{Route, RoutingProtocol};
+use super::{Point, SimTime};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
 use std::cell::Cell;
 
 pub type NodeId = usize;
 
+/// Number of an autonomous system.
+pub type AsNumber = u32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Router,
+    Endpoint,
+}
+
 pub trait Node {
     fn get_id(&self) -> NodeId;
+    fn get_kind(&self) -> NodeKind;
     fn get_position(&self) -> Point;
     fn corresponds_to_position(&self, position: Point) -> bool;
-    fn draw(&self, renderer: &mut Renderer) -> Result<(), String>;
     fn will_receive(&self, interface: NodeInterfaceId, packet: &Packet) -> bool;
     fn receive(
         &mut self,
         interface: NodeInterfaceId,
         packet: Packet,
+        time: SimTime,
+        rng: &mut dyn RngCore,
     ) -> Vec<(NodeInterfaceId, Packet)>;
-    fn get_known_route_interface(&self, destination: NodeId) -> NodeInterfaceId;
-    fn set_known_route(&mut self, destination: NodeId, send_to: NodeId);
-    fn get_interface(&self, interface: NodeInterfaceId) -> &NodeInterface;
-    fn connect_interface(&self, interface: NodeInterfaceId, ether: EtherId);
-    fn create_interface(&mut self, id: NodeInterfaceId) -> Result<NodeInterfaceId, String>;
+    fn tick(&mut self, time: SimTime) -> Vec<(NodeInterfaceId, Packet)>;
+    fn get_known_route_interface(
+        &mut self,
+        source: NodeId,
+        destination: NodeId,
+        rng: &mut dyn RngCore,
+    ) -> Option<NodeInterfaceId>;
+    fn set_known_route(&mut self, destination: NodeId, interfaces: Vec<NodeInterfaceId>);
+    /// Drops the routes to a removed node and the interfaces that are no longer connected
+    /// from the others.
+    fn forget_routes(&mut self, removed: NodeId);
+    fn get_routes(&self) -> Vec<Route>;
+    fn set_routing_protocol(&mut self, protocol: Option<Box<dyn RoutingProtocol>>);
+    fn is_up(&self) -> bool;
+    /// A node that is down neither forwards nor originates anything.
+    fn set_up(&mut self, up: bool);
+    fn get_autonomous_system(&self) -> Option<AsNumber>;
+    fn set_autonomous_system(&mut self, autonomous_system: Option<AsNumber>);
+    fn get_interface(&self, interface: NodeInterfaceId) -> Option<&NodeInterface>;
+    fn connect_interface(
+        &self,
+        interface: NodeInterfaceId,
+        ether: EtherId,
+    ) -> Result<(), NetworkError>;
+    fn create_interface(&mut self, id: NodeInterfaceId) -> Result<NodeInterfaceId, NetworkError>;
 }
 
 pub type NodeInterfaceId = String;
 
+/// The interface through which packets sent by a node itself enter it.
+pub const LOCALHOST: &str = "localhost";
+
 #[derive(Clone)]
 pub struct NodeInterface {
     owner_node: NodeId,
     id_in_owner: NodeInterfaceId,
     connected_ether: Cell<Option<EtherId>>,
+    link_up: Cell<bool>,
 }
 
 impl NodeInterface {
@@ -42,6 +81,7 @@ impl NodeInterface {
             owner_node,
             id_in_owner,
             connected_ether: Cell::new(None),
+            link_up: Cell::new(true),
         }
     }
 
@@ -49,6 +89,10 @@ impl NodeInterface {
         self.connected_ether.replace(Some(ether));
     }
 
+    pub fn disconnect(&self) {
+        self.connected_ether.replace(None);
+    }
+
     pub fn get_to_owner(&self) -> (NodeId, NodeInterfaceId) {
         (self.owner_node, self.id_in_owner.clone())
     }
@@ -56,4 +100,13 @@ impl NodeInterface {
     pub fn get_connected_ether(&self) -> Option<EtherId> {
         self.connected_ether.get()
     }
+
+    /// Whether the ether behind the interface is up, as a carrier signal would tell.
+    pub fn is_link_up(&self) -> bool {
+        self.link_up.get()
+    }
+
+    pub fn set_link_up(&self, up: bool) {
+        self.link_up.set(up);
+    }
 }
@@ -1,12 +1,14 @@
+use super::super::error::NetworkError;
 use super::super::ether::{distance_between, EtherId};
-use super::super::node::{Node, NodeId, NodeInterface, NodeInterfaceId};
-use super::super::packet::Packet;
-use crate::app::{Renderer, BACK, DELETE, DIJKSTRA};
+use super::super::node::{AsNumber, Node, NodeId, NodeInterface, NodeInterfaceId, NodeKind};
+use super::super::packet::{random_uuid, Packet, Payload, DEFAULT_TTL, LINK_LOCAL};
+use super::super::routing::{Route, RouterContext, RoutingProtocol};
+use crate::network::{Point, SimTime, BACK, DELETE, DIJKSTRA, ROUND_ROBIN, TIME_EXCEEDED};
 use rand::seq::SliceRandom;
-use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::{Texture, TextureQuery};
+use rand::RngCore;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::sync::atomic::Ordering;
 use uuid::Uuid;
 
@@ -14,8 +16,12 @@ pub struct RouterNode {
     id: NodeId,
     position: Point,
     interfaces: HashMap<NodeInterfaceId, NodeInterface>,
-    known_routes: HashMap<NodeId, NodeInterfaceId>,
-    pub(super) texture: Texture,
+    known_routes: HashMap<NodeId, Vec<NodeInterfaceId>>,
+    /// Next equal-cost route to use per destination, in round-robin mode.
+    round_robin: HashMap<NodeId, usize>,
+    protocol: Option<Box<dyn RoutingProtocol>>,
+    autonomous_system: Option<AsNumber>,
+    up: bool,
 }
 
 impl Node for RouterNode {
@@ -23,6 +29,10 @@ impl Node for RouterNode {
         self.id
     }
 
+    fn get_kind(&self) -> NodeKind {
+        NodeKind::Router
+    }
+
     fn get_position(&self) -> Point {
         self.position
     }
@@ -31,115 +41,217 @@ impl Node for RouterNode {
         distance_between((position, self.get_position())) < 35.0
     }
 
-    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
-        let position = self.get_position();
-        renderer.canvas.copy(
-            &renderer.node_texture,
-            None,
-            Some(Rect::from_center(position, 50, 50)),
-        )?;
-        let TextureQuery { width, height, .. } = self.texture.query();
-        renderer.canvas.copy(
-            &self.texture,
-            None,
-            Some(Rect::from_center(position.offset(0, -10), width, height)),
-        )
-    }
-
     fn will_receive(&self, _interface: NodeInterfaceId, packet: &Packet) -> bool {
         packet.current_sender != self.id
     }
 
     fn receive(
         &mut self,
-        _interface: NodeInterfaceId,
+        interface: NodeInterfaceId,
         packet: Packet,
+        time: SimTime,
+        rng: &mut dyn RngCore,
     ) -> Vec<(NodeInterfaceId, Packet)> {
-        // println!(
-        //     "{} {:3} > {:3} : RX {:3} | {}",
-        //     packet.uuid, packet.source, packet.destination, self.id, interface,
-        // );
+        if !self.up {
+            return vec![];
+        }
+        if packet.destination == LINK_LOCAL {
+            let interfaces = self.connected_interfaces();
+            let sent = match self.protocol.as_mut() {
+                Some(protocol) => protocol.receive(
+                    RouterContext {
+                        id: self.id,
+                        autonomous_system: self.autonomous_system,
+                        interfaces: &interfaces,
+                        known_routes: &mut self.known_routes,
+                    },
+                    &interface,
+                    packet.current_sender,
+                    &packet.payload,
+                    time,
+                ),
+                None => vec![],
+            };
+            return self.control_packets(sent);
+        }
         if DELETE.load(Ordering::Relaxed) {
             return vec![];
         }
         if packet.destination != self.get_id() {
-            let out_interface = self.get_known_route_interface(packet.destination);
-            vec![(
-                out_interface,
-                Packet {
-                    uuid: packet.uuid,
-                    source: packet.source,
-                    current_sender: self.get_id(),
-                    destination: packet.destination,
-                },
-            )]
-        } else {
-            if BACK.load(Ordering::Relaxed) {
-                vec![(
-                    self.get_known_route_interface(packet.source),
-                    Packet {
-                        uuid: Uuid::new_v4(),
-                        source: packet.destination,
-                        current_sender: self.get_id(),
-                        destination: packet.source,
-                    },
-                )]
+            // Packets are only counted down past the node that sent them.
+            let ttl = if packet.current_sender == self.id {
+                packet.ttl
             } else {
-                vec![]
+                packet.ttl.saturating_sub(1)
+            };
+            if ttl == 0 {
+                return if TIME_EXCEEDED.load(Ordering::Relaxed) && packet.is_data() {
+                    self.time_exceeded(&packet, rng)
+                } else {
+                    vec![]
+                };
             }
+            self.get_known_route_interface(packet.source, packet.destination, rng)
+                .map(|out_interface| {
+                    (
+                        out_interface,
+                        Packet {
+                            uuid: packet.uuid,
+                            source: packet.source,
+                            current_sender: self.get_id(),
+                            destination: packet.destination,
+                            payload: packet.payload,
+                            size: packet.size,
+                            ttl,
+                            path: packet.path,
+                        },
+                    )
+                })
+                .into_iter()
+                .collect()
+        } else if BACK.load(Ordering::Relaxed) && packet.is_data() {
+            self.get_known_route_interface(packet.destination, packet.source, rng)
+                .map(|out_interface| {
+                    (
+                        out_interface,
+                        Packet {
+                            uuid: random_uuid(rng),
+                            source: packet.destination,
+                            current_sender: self.get_id(),
+                            destination: packet.source,
+                            payload: Payload::Data,
+                            size: packet.size,
+                            ttl: DEFAULT_TTL,
+                            path: vec![],
+                        },
+                    )
+                })
+                .into_iter()
+                .collect()
+        } else {
+            vec![]
         }
     }
 
-    fn get_known_route_interface(&self, destination: NodeId) -> NodeInterfaceId {
-        let known_route_interface = if DIJKSTRA.load(Ordering::Relaxed) {
-            self.known_routes.get(&destination)
-        } else {
-            None
+    fn tick(&mut self, time: SimTime) -> Vec<(NodeInterfaceId, Packet)> {
+        if !self.up {
+            return vec![];
+        }
+        let interfaces = self.connected_interfaces();
+        let sent = match self.protocol.as_mut() {
+            Some(protocol) => protocol.tick(
+                RouterContext {
+                    id: self.id,
+                    autonomous_system: self.autonomous_system,
+                    interfaces: &interfaces,
+                    known_routes: &mut self.known_routes,
+                },
+                time,
+            ),
+            None => vec![],
         };
+        self.control_packets(sent)
+    }
 
-        known_route_interface
-            .unwrap_or_else(|| {
-                let keys: Vec<&String> = self.interfaces.keys().collect();
-                *keys.choose(&mut rand::thread_rng()).unwrap()
-            })
-            .to_string()
+    fn get_known_route_interface(
+        &mut self,
+        source: NodeId,
+        destination: NodeId,
+        rng: &mut dyn RngCore,
+    ) -> Option<NodeInterfaceId> {
+        if DIJKSTRA.load(Ordering::Relaxed) {
+            // Without a route the packet is dropped, as a real router would.
+            let interfaces = self.known_routes.get(&destination)?;
+            let choice = if interfaces.len() < 2 {
+                0
+            } else if ROUND_ROBIN.load(Ordering::Relaxed) {
+                let next = self.round_robin.entry(destination).or_insert(0);
+                *next = (*next + 1) % interfaces.len();
+                *next
+            } else {
+                // Packets of one flow stay on one path, so they aren't reordered.
+                let mut hasher = DefaultHasher::new();
+                (source, destination).hash(&mut hasher);
+                hasher.finish() as usize % interfaces.len()
+            };
+            return interfaces.get(choice).cloned();
+        }
+
+        self.connected_interfaces().choose(rng).cloned()
     }
 
-    fn set_known_route(&mut self, destination: NodeId, send_to: NodeId) {
-        /*let keys: Vec<&String> = self.interfaces.keys().collect();
-        let mut keys: Vec<usize> = keys
-            .iter()
-            .map(|s| (*s).rsplit_once('-').unwrap().1.parse::<usize>().unwrap())
-            .collect();
-        keys.sort();
-        let key = if destination == 16 {
-            keys.last()
-        } else {
-            keys.first()
-        };
-        let interface = self.id.to_string() + "-" + &key.unwrap().to_string();*/
-        let interface = format!("{}-{}", self.get_id(), send_to);
-        self.known_routes.insert(destination, interface);
+    fn set_known_route(&mut self, destination: NodeId, interfaces: Vec<NodeInterfaceId>) {
+        self.known_routes.insert(destination, interfaces);
+    }
+
+    fn forget_routes(&mut self, removed: NodeId) {
+        self.known_routes.remove(&removed);
+        let connected = self.connected_interfaces();
+        for interfaces in self.known_routes.values_mut() {
+            interfaces.retain(|interface| connected.contains(interface));
+        }
+    }
+
+    fn get_routes(&self) -> Vec<Route> {
+        match &self.protocol {
+            Some(protocol) => protocol.routes(),
+            None => {
+                let mut routes: Vec<Route> = self
+                    .known_routes
+                    .iter()
+                    .map(|(destination, interfaces)| Route {
+                        destination: *destination,
+                        interfaces: interfaces.clone(),
+                        metric: None,
+                        as_path: vec![],
+                    })
+                    .collect();
+                routes.sort_by_key(|route| route.destination);
+                routes
+            }
+        }
+    }
+
+    fn set_routing_protocol(&mut self, protocol: Option<Box<dyn RoutingProtocol>>) {
+        self.known_routes.clear();
+        self.protocol = protocol;
+    }
+
+    fn is_up(&self) -> bool {
+        self.up
+    }
+
+    fn set_up(&mut self, up: bool) {
+        self.up = up;
     }
 
-    fn get_interface(&self, interface: NodeInterfaceId) -> &NodeInterface {
-        self.interfaces.get(&interface).unwrap()
+    fn get_autonomous_system(&self) -> Option<AsNumber> {
+        self.autonomous_system
     }
 
-    fn connect_interface(&self, interface: NodeInterfaceId, ether: EtherId) {
+    fn set_autonomous_system(&mut self, autonomous_system: Option<AsNumber>) {
+        self.autonomous_system = autonomous_system;
+    }
+
+    fn get_interface(&self, interface: NodeInterfaceId) -> Option<&NodeInterface> {
+        self.interfaces.get(&interface)
+    }
+
+    fn connect_interface(
+        &self,
+        interface: NodeInterfaceId,
+        ether: EtherId,
+    ) -> Result<(), NetworkError> {
         self.interfaces
             .get(&interface)
-            .expect(&*format!(
-                "No interface '{}' in node '{}'!",
-                interface,
-                self.get_id()
-            ))
-            .connect(ether)
+            .ok_or_else(|| NetworkError::InterfaceNotFound(self.get_id(), interface.clone()))?
+            .connect(ether);
+        Ok(())
     }
 
-    fn create_interface(&mut self, id: NodeInterfaceId) -> Result<NodeInterfaceId, String> {
+    fn create_interface(&mut self, id: NodeInterfaceId) -> Result<NodeInterfaceId, NetworkError> {
         if self.interfaces.contains_key(&id) {
-            Err("Interface already created!".to_string())
+            Err(NetworkError::InterfaceAlreadyExists(self.get_id(), id))
         } else {
             self.interfaces
                 .insert(id.clone(), NodeInterface::new(self.get_id(), id.clone()));
@@ -149,18 +261,80 @@ impl Node for RouterNode {
 }
 
 impl RouterNode {
-    pub fn new(renderer: &mut Renderer, id: NodeId, position: Point) -> Self {
-        let texture = renderer
-            .make_text(&id.to_string(), position, Color::RED)
-            .unwrap()
-            .0;
-
+    pub fn new(id: NodeId, position: Point) -> Self {
         Self {
             id,
             position,
             interfaces: HashMap::new(),
             known_routes: HashMap::new(),
-            texture,
+            round_robin: HashMap::new(),
+            protocol: None,
+            autonomous_system: None,
+            up: true,
         }
     }
+
+    fn connected_interfaces(&self) -> Vec<NodeInterfaceId> {
+        let mut interfaces: Vec<NodeInterfaceId> = self
+            .interfaces
+            .iter()
+            .filter(|(_, interface)| {
+                interface.get_connected_ether().is_some() && interface.is_link_up()
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        interfaces.sort();
+        interfaces
+    }
+
+    fn time_exceeded(
+        &mut self,
+        expired: &Packet,
+        rng: &mut dyn RngCore,
+    ) -> Vec<(NodeInterfaceId, Packet)> {
+        let payload = Payload::TimeExceeded {
+            original: expired.uuid,
+        };
+        self.get_known_route_interface(self.id, expired.source, rng)
+            .map(|out_interface| {
+                (
+                    out_interface,
+                    Packet {
+                        uuid: random_uuid(rng),
+                        source: self.id,
+                        current_sender: self.id,
+                        destination: expired.source,
+                        size: payload.size(),
+                        ttl: DEFAULT_TTL,
+                        path: vec![],
+                        payload,
+                    },
+                )
+            })
+            .into_iter()
+            .collect()
+    }
+
+    fn control_packets(
+        &self,
+        sent: Vec<(NodeInterfaceId, Payload)>,
+    ) -> Vec<(NodeInterfaceId, Packet)> {
+        sent.into_iter()
+            .map(|(interface, payload)| {
+                (
+                    interface,
+                    Packet {
+                        uuid: Uuid::nil(),
+                        source: self.id,
+                        current_sender: self.id,
+                        destination: LINK_LOCAL,
+                        size: payload.size(),
+                        ttl: 1,
+                        path: vec![],
+                        payload,
+                    },
+                )
+            })
+            .collect()
+    }
 }
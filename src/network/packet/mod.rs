@@ -1,7 +1,54 @@
-use super::node::NodeId;
-use crate::app::Renderer;
-use sdl2::rect::{Point, Rect};
-use uuid::Uuid;
+use super::node::{AsNumber, NodeId, NodeInterfaceId};
+use super::routing::link_state::LinkStateAdvertisement;
+use super::routing::path_vector::PathVectorRoute;
+use super::SimTime;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use uuid::{Builder, Uuid, Variant, Version};
+
+/// Size in bytes of data packets whose size isn't given.
+pub const PACKET_SIZE: u64 = 1500;
+
+/// Size in bytes of the headers every packet carries.
+pub const HEADER_SIZE: u64 = 20;
+
+/// Hops a packet may take unless told otherwise.
+pub const DEFAULT_TTL: u8 = 64;
+
+/// Destination of routing protocol packets, which are only meant for the directly attached peers.
+pub const LINK_LOCAL: NodeId = NodeId::MAX;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Payload {
+    Data,
+    /// `(destination, metric)` pairs advertised by a distance-vector router.
+    DistanceVector(Vec<(NodeId, u32)>),
+    /// Neighbor discovery; the send time lets the receiver measure the link's cost.
+    Hello {
+        sent: SimTime,
+    },
+    LinkState(LinkStateAdvertisement),
+    PathVector {
+        autonomous_system: Option<AsNumber>,
+        routes: Vec<PathVectorRoute>,
+    },
+    /// ICMP-like notice sent back to the source of a packet whose TTL ran out.
+    TimeExceeded {
+        original: Uuid,
+    },
+}
+
+/// What a packet carries, without the contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketKind {
+    Data,
+    DistanceVector,
+    Hello,
+    LinkState,
+    PathVector,
+    TimeExceeded,
+}
 
 #[derive(Clone)]
 pub struct Packet {
@@ -9,14 +56,66 @@ pub struct Packet {
     pub source: NodeId,
     pub current_sender: NodeId,
     pub destination: NodeId,
+    pub payload: Payload,
+    /// Bytes on the wire, which together with the link's bandwidth gives the serialization delay.
+    pub size: u64,
+    /// Hops left before the packet is discarded.
+    pub ttl: u8,
+    /// Every node the packet reached so far, starting with its source.
+    pub path: Vec<Hop>,
+}
+
+/// A node receiving a packet on one of its interfaces.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Hop {
+    pub node: NodeId,
+    pub interface: NodeInterfaceId,
+    pub time: SimTime,
+}
+
+impl Payload {
+    pub fn kind(&self) -> PacketKind {
+        match self {
+            Payload::Data => PacketKind::Data,
+            Payload::DistanceVector(_) => PacketKind::DistanceVector,
+            Payload::Hello { .. } => PacketKind::Hello,
+            Payload::LinkState(_) => PacketKind::LinkState,
+            Payload::PathVector { .. } => PacketKind::PathVector,
+            Payload::TimeExceeded { .. } => PacketKind::TimeExceeded,
+        }
+    }
+
+    /// Rough encoded size of routing protocol packets, headers included.
+    pub fn size(&self) -> u64 {
+        HEADER_SIZE
+            + match self {
+                Payload::Data => PACKET_SIZE - HEADER_SIZE,
+                Payload::DistanceVector(entries) => 8 * entries.len() as u64,
+                Payload::Hello { .. } => 8,
+                Payload::LinkState(advertisement) => 16 + 16 * advertisement.links.len() as u64,
+                // The original packet's header is quoted.
+                Payload::TimeExceeded { .. } => 8 + HEADER_SIZE,
+                Payload::PathVector { routes, .. } => {
+                    4 + routes
+                        .iter()
+                        .map(|route| 8 + 4 * (route.as_path.len() + route.node_path.len()) as u64)
+                        .sum::<u64>()
+                }
+            }
+    }
 }
 
 impl Packet {
-    pub fn draw(&self, renderer: &mut Renderer, position: Point) -> Result<(), String> {
-        renderer.canvas.copy(
-            &renderer.packet_texture,
-            None,
-            Some(Rect::from_center(position, 30, 30)),
-        )
+    pub fn is_data(&self) -> bool {
+        self.payload == Payload::Data
     }
 }
+
+pub fn random_uuid(rng: &mut dyn RngCore) -> Uuid {
+    let mut bytes = [0u8; 16];
+    rng.fill_bytes(&mut bytes);
+    Builder::from_bytes(bytes)
+        .set_variant(Variant::RFC4122)
+        .set_version(Version::Random)
+        .build()
+}
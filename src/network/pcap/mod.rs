@@ -0,0 +1,207 @@
+use super::ether::EtherId;
+use super::node::NodeId;
+use super::packet::{Packet, Payload, LINK_LOCAL};
+use super::SimTime;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+const ETHERNET_HEADER_SIZE: usize = 14;
+const IPV4_HEADER_SIZE: usize = 20;
+/// Longest packet an IPv4 header can describe; larger ones are captured cut to it.
+const MAX_IPV4_SIZE: usize = 0xffff;
+/// Longer than any frame, so that none is truncated further.
+const SNAPSHOT_LENGTH: u32 = 262144;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+/// Protocol numbers reserved for experimentation (RFC 3692): data, and everything else.
+const PROTOCOL_DATA: u8 = 253;
+const PROTOCOL_CONTROL: u8 = 254;
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// Locally administered address of a node's interfaces.
+pub fn mac_address(node: NodeId) -> [u8; 6] {
+    let [a, b, c, d] = (node as u32).to_be_bytes();
+    [0x02, 0x00, a, b, c, d]
+}
+
+/// Node `n` is `10.x.y.z` with `n` in the lower 24 bits; link-local packets go to the broadcast address.
+pub fn ipv4_address(node: NodeId) -> [u8; 4] {
+    if node == LINK_LOCAL {
+        return [255, 255, 255, 255];
+    }
+    let [_, b, c, d] = (node as u32).to_be_bytes();
+    [10, b, c, d]
+}
+
+fn checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Bytes of the packet's frame on the wire, even if it doesn't fit in IPv4.
+pub fn frame_length(packet: &Packet) -> usize {
+    let minimum = IPV4_HEADER_SIZE + packet.uuid.as_bytes().len();
+    ETHERNET_HEADER_SIZE + (packet.size as usize).max(minimum)
+}
+
+/// A packet as an Ethernet frame sent from one node to the next: an IPv4 header between the
+/// packet's ends, then its UUID, padded with zeroes to the packet's size or `MAX_IPV4_SIZE`.
+pub fn frame(packet: &Packet, from: NodeId, to: NodeId) -> Vec<u8> {
+    let uuid = packet.uuid.as_bytes();
+    let total_length = (frame_length(packet) - ETHERNET_HEADER_SIZE).min(MAX_IPV4_SIZE);
+    let mut frame = Vec::with_capacity(ETHERNET_HEADER_SIZE + total_length);
+
+    frame.extend_from_slice(&mac_address(to));
+    frame.extend_from_slice(&mac_address(from));
+    frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+    let protocol = match packet.payload {
+        Payload::Data => PROTOCOL_DATA,
+        _ => PROTOCOL_CONTROL,
+    };
+    let mut header = [0u8; IPV4_HEADER_SIZE];
+    header[0] = 0x45;
+    header[2..4].copy_from_slice(&(total_length as u16).to_be_bytes());
+    // The identification lets packets be followed from link to link.
+    header[4..6].copy_from_slice(&uuid[14..16]);
+    // Don't fragment.
+    header[6] = 0x40;
+    header[8] = packet.ttl;
+    header[9] = protocol;
+    header[12..16].copy_from_slice(&ipv4_address(packet.source));
+    header[16..20].copy_from_slice(&ipv4_address(packet.destination));
+    let sum = checksum(&header);
+    header[10..12].copy_from_slice(&sum.to_be_bytes());
+    frame.extend_from_slice(&header);
+
+    frame.extend_from_slice(uuid);
+    frame.resize(ETHERNET_HEADER_SIZE + total_length, 0);
+    frame
+}
+
+/// Writes frames in the classic libpcap format.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<PcapWriter<W>> {
+        writer.write_all(&0xa1b2c3d4u32.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        // Time zone offset and timestamp accuracy.
+        writer.write_all(&0i32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&SNAPSHOT_LENGTH.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+        Ok(PcapWriter { writer })
+    }
+
+    /// Simulated milliseconds are written as if the run started at the epoch; `length` is the
+    /// frame's real length when it was cut.
+    pub fn write_frame(&mut self, time: SimTime, frame: &[u8], length: usize) -> io::Result<()> {
+        self.writer
+            .write_all(&((time / 1000) as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&((time % 1000 * 1000) as u32).to_le_bytes())?;
+        self.writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&(length.max(frame.len()) as u32).to_le_bytes())?;
+        self.writer.write_all(frame)
+    }
+}
+
+/// One capture file per link, `link-<ether>.pcap` in the given directory, created when the
+/// first packet is sent over the link.
+pub struct Capture {
+    directory: PathBuf,
+    files: HashMap<EtherId, PcapWriter<BufWriter<File>>>,
+}
+
+impl Capture {
+    pub fn new(directory: impl Into<PathBuf>) -> io::Result<Capture> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Capture {
+            directory,
+            files: HashMap::new(),
+        })
+    }
+
+    pub fn capture(
+        &mut self,
+        ether: EtherId,
+        time: SimTime,
+        from: NodeId,
+        to: NodeId,
+        packet: &Packet,
+    ) -> io::Result<()> {
+        let writer = match self.files.get_mut(&ether) {
+            Some(writer) => writer,
+            None => {
+                let path = self.directory.join(format!("link-{}.pcap", ether));
+                let writer = PcapWriter::new(BufWriter::new(File::create(path)?))?;
+                self.files.entry(ether).or_insert(writer)
+            }
+        };
+        writer.write_frame(time, &frame(packet, from, to), frame_length(packet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn packet(size: u64) -> Packet {
+        Packet {
+            uuid: Uuid::from_u128(7),
+            source: 1,
+            current_sender: 1,
+            destination: 2,
+            payload: Payload::Data,
+            size,
+            ttl: 64,
+            path: vec![],
+        }
+    }
+
+    #[test]
+    fn frame_headers() {
+        let frame = frame(&packet(100), 1, 2);
+        assert_eq!(frame.len(), ETHERNET_HEADER_SIZE + 100);
+        assert_eq!(frame[..6], mac_address(2));
+        let header = &frame[ETHERNET_HEADER_SIZE..ETHERNET_HEADER_SIZE + IPV4_HEADER_SIZE];
+        assert_eq!(checksum(header), 0);
+        assert_eq!(header[2..4], 100u16.to_be_bytes());
+        assert_eq!(header[16..20], [10, 0, 0, 2]);
+    }
+
+    #[test]
+    fn oversized_packets_keep_their_length() {
+        let packet = packet(100_000);
+        let frame = frame(&packet, 1, 2);
+        assert_eq!(frame.len(), ETHERNET_HEADER_SIZE + MAX_IPV4_SIZE);
+
+        let mut writer = PcapWriter::new(vec![]).unwrap();
+        writer
+            .write_frame(1500, &frame, frame_length(&packet))
+            .unwrap();
+        let bytes = writer.writer;
+        let field =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(field(16), SNAPSHOT_LENGTH);
+        // Seconds, microseconds, captured and original lengths after the 24-byte header.
+        assert_eq!((field(24), field(28)), (1, 500_000));
+        assert_eq!(field(32) as usize, frame.len());
+        assert_eq!(field(36) as usize, ETHERNET_HEADER_SIZE + 100_000);
+        assert!(field(32) <= SNAPSHOT_LENGTH);
+    }
+}
@@ -0,0 +1,289 @@
+use super::ether::EtherId;
+use super::packet::Packet;
+use super::{Point, SimTime};
+use rand::{Rng, RngCore};
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// What an interface does with packets arriving while its queue fills up.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    /// Only packets that don't fit are dropped.
+    #[default]
+    DropTail,
+    /// Random early detection: packets are dropped with a probability growing with the
+    /// average queue depth, so that senders notice congestion before the queue is full.
+    Red,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RedSettings {
+    /// Average depth below which nothing is dropped early.
+    pub min_threshold: f64,
+    /// Average depth from which everything is dropped.
+    pub max_threshold: f64,
+    /// Drop probability just below `max_threshold`.
+    pub max_probability: f64,
+    /// Weight of the current depth in the moving average.
+    pub weight: f64,
+}
+
+impl Default for RedSettings {
+    fn default() -> Self {
+        RedSettings {
+            min_threshold: 5.0,
+            max_threshold: 15.0,
+            max_probability: 0.1,
+            weight: 0.2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(from = "QueueSettingsFields")]
+pub struct QueueSettings {
+    /// Packets an interface can hold while its link is busy.
+    pub capacity: usize,
+    pub policy: DropPolicy,
+    pub red: RedSettings,
+}
+
+/// `QueueSettings` as written in scenarios, with the RED settings next to the others.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct QueueSettingsFields {
+    capacity: usize,
+    policy: DropPolicy,
+    min_threshold: f64,
+    max_threshold: f64,
+    max_probability: f64,
+    weight: f64,
+}
+
+impl Default for QueueSettings {
+    fn default() -> Self {
+        QueueSettings {
+            capacity: 32,
+            policy: DropPolicy::DropTail,
+            red: RedSettings::default(),
+        }
+    }
+}
+
+impl Default for QueueSettingsFields {
+    fn default() -> Self {
+        let QueueSettings {
+            capacity,
+            policy,
+            red,
+        } = QueueSettings::default();
+        QueueSettingsFields {
+            capacity,
+            policy,
+            min_threshold: red.min_threshold,
+            max_threshold: red.max_threshold,
+            max_probability: red.max_probability,
+            weight: red.weight,
+        }
+    }
+}
+
+impl From<QueueSettingsFields> for QueueSettings {
+    fn from(fields: QueueSettingsFields) -> Self {
+        QueueSettings {
+            capacity: fields.capacity,
+            policy: fields.policy,
+            red: RedSettings {
+                min_threshold: fields.min_threshold,
+                max_threshold: fields.max_threshold,
+                max_probability: fields.max_probability,
+                weight: fields.weight,
+            },
+        }
+    }
+}
+
+/// Packets waiting for an interface's link to be free, first in first out.
+pub struct OutputQueue {
+    ether: EtherId,
+    position: Point,
+    packets: VecDeque<Packet>,
+    busy_until: SimTime,
+    average: f64,
+}
+
+impl OutputQueue {
+    pub fn new(ether: EtherId, position: Point) -> OutputQueue {
+        OutputQueue {
+            ether,
+            position,
+            packets: VecDeque::new(),
+            busy_until: 0,
+            average: 0.0,
+        }
+    }
+
+    pub fn get_ether(&self) -> EtherId {
+        self.ether
+    }
+
+    pub fn get_position(&self) -> Point {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub fn packets(&self) -> impl Iterator<Item = &Packet> {
+        self.packets.iter()
+    }
+
+    /// Queues the packet, or gives it back if the policy drops it.
+    pub fn enqueue(
+        &mut self,
+        packet: Packet,
+        settings: &QueueSettings,
+        rng: &mut dyn RngCore,
+    ) -> Result<(), Packet> {
+        let depth = self.packets.len();
+        if depth >= settings.capacity {
+            return Err(packet);
+        }
+        if settings.policy == DropPolicy::Red {
+            let red = &settings.red;
+            self.average = (1.0 - red.weight) * self.average + red.weight * depth as f64;
+            let probability = if self.average < red.min_threshold {
+                0.0
+            } else if self.average >= red.max_threshold {
+                1.0
+            } else {
+                red.max_probability * (self.average - red.min_threshold)
+                    / (red.max_threshold - red.min_threshold)
+            };
+            if probability > 0.0 && rng.gen_bool(probability.min(1.0)) {
+                return Err(packet);
+            }
+        }
+        self.packets.push_back(packet);
+        Ok(())
+    }
+
+    /// The next packet to put on the link along with the sender's position, if the link is free.
+    pub fn dequeue(&mut self, time: SimTime) -> Option<(Point, Packet)> {
+        if time < self.busy_until {
+            return None;
+        }
+        self.packets
+            .pop_front()
+            .map(|packet| (self.position, packet))
+    }
+
+    pub fn set_busy_until(&mut self, time: SimTime) {
+        self.busy_until = self.busy_until.max(time);
+    }
+
+    pub fn clear(&mut self) -> Vec<Packet> {
+        self.packets.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::packet::{Payload, PACKET_SIZE};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use uuid::Uuid;
+
+    fn packet(number: u128) -> Packet {
+        Packet {
+            uuid: Uuid::from_u128(number),
+            source: 0,
+            current_sender: 0,
+            destination: 1,
+            payload: Payload::Data,
+            size: PACKET_SIZE,
+            ttl: 64,
+            path: vec![],
+        }
+    }
+
+    fn settings(capacity: usize, policy: DropPolicy) -> QueueSettings {
+        QueueSettings {
+            capacity,
+            policy,
+            ..QueueSettings::default()
+        }
+    }
+
+    #[test]
+    fn drop_tail_drops_what_does_not_fit() {
+        let (mut queue, mut rng) = (
+            OutputQueue::new(0, Point::new(0, 0)),
+            StdRng::seed_from_u64(1),
+        );
+        let settings = settings(3, DropPolicy::DropTail);
+        let results: Vec<bool> = (0..5)
+            .map(|number| queue.enqueue(packet(number), &settings, &mut rng).is_ok())
+            .collect();
+        assert_eq!(results, [true, true, true, false, false]);
+
+        let order: Vec<u128> = std::iter::from_fn(|| queue.dequeue(0))
+            .map(|(_, packet)| packet.uuid.as_u128())
+            .collect();
+        assert_eq!(order, [0, 1, 2]);
+    }
+
+    #[test]
+    fn busy_link_holds_packets_back() {
+        let (mut queue, mut rng) = (
+            OutputQueue::new(0, Point::new(0, 0)),
+            StdRng::seed_from_u64(1),
+        );
+        let settings = settings(3, DropPolicy::DropTail);
+        assert!(queue.enqueue(packet(0), &settings, &mut rng).is_ok());
+        queue.set_busy_until(10);
+        assert!(queue.dequeue(9).is_none());
+        assert!(queue.dequeue(10).is_some());
+    }
+
+    #[test]
+    fn red_drops_early_between_thresholds() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut settings = settings(100, DropPolicy::Red);
+        settings.red = RedSettings {
+            min_threshold: 2.0,
+            max_threshold: 50.0,
+            max_probability: 0.5,
+            weight: 1.0,
+        };
+
+        // Below the minimum threshold nothing is dropped.
+        let mut queue = OutputQueue::new(0, Point::new(0, 0));
+        for number in 0..2 {
+            assert!(queue.enqueue(packet(number), &settings, &mut rng).is_ok());
+        }
+
+        // Between the thresholds some packets are dropped before the queue is full.
+        let dropped = (2..60)
+            .filter(|number| queue.enqueue(packet(*number), &settings, &mut rng).is_err())
+            .count();
+        assert!(dropped > 0);
+        assert!(queue.len() < settings.capacity);
+
+        // From the maximum threshold everything is.
+        settings.red.max_threshold = 3.0;
+        let mut queue = OutputQueue::new(0, Point::new(0, 0));
+        for number in 0..3 {
+            queue.enqueue(packet(number), &settings, &mut rng).ok();
+        }
+        assert_eq!(queue.len(), 3);
+        assert!(queue.enqueue(packet(3), &settings, &mut rng).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use super::ether::EtherId;
+use super::node::{AsNumber, NodeId, NodeKind};
+use super::packet::PacketKind;
+use super::{position_along, Impairment, Point, SimTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, Write};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedNode {
+    pub id: NodeId,
+    pub kind: NodeKind,
+    pub position: Point,
+    pub autonomous_system: Option<AsNumber>,
+    pub up: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEther {
+    pub id: EtherId,
+    pub segments: Vec<(Point, Point)>,
+    pub up: bool,
+}
+
+/// The network as it was when the recording started.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub seed: u64,
+    pub time: SimTime,
+    pub nodes: Vec<RecordedNode>,
+    pub ethers: Vec<RecordedEther>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedTransmission {
+    pub id: u64,
+    pub ether: EtherId,
+    pub from: Point,
+    pub to: Point,
+    pub duration: SimTime,
+    pub kind: PacketKind,
+    pub impairment: Option<Impairment>,
+    /// Data packets sent over the ether so far, this one included.
+    pub load: u64,
+}
+
+/// A change to what is drawn.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    TransmissionStart(RecordedTransmission),
+    TransmissionEnd { id: u64 },
+    LinkState { ether: EtherId, up: bool },
+    NodeState { node: NodeId, up: bool },
+    EtherRemoved { ether: EtherId },
+    NodeRemoved { node: NodeId },
+}
+
+#[derive(Serialize, Deserialize)]
+struct TimedEvent {
+    time: SimTime,
+    #[serde(flatten)]
+    event: RecordedEvent,
+}
+
+/// Writes a recording as JSON lines: the header, then every event.
+pub struct Recorder {
+    writer: Box<dyn Write>,
+    started: bool,
+}
+
+impl Recorder {
+    pub fn new(writer: impl Write + 'static) -> Recorder {
+        Recorder {
+            writer: Box::new(writer),
+            started: false,
+        }
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    pub fn start(&mut self, header: &RecordingHeader) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, header)?;
+        self.writer.write_all(b"\n")?;
+        self.started = true;
+        Ok(())
+    }
+
+    pub fn record(&mut self, time: SimTime, event: &RecordedEvent) -> io::Result<()> {
+        let event = TimedEvent {
+            time,
+            event: event.clone(),
+        };
+        serde_json::to_writer(&mut self.writer, &event)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+pub struct Recording {
+    pub header: RecordingHeader,
+    /// Events in time order.
+    pub events: Vec<(SimTime, RecordedEvent)>,
+}
+
+impl Recording {
+    pub fn load(reader: impl BufRead) -> Result<Recording, String> {
+        let mut lines = reader.lines().enumerate();
+        let header = match lines.next() {
+            Some((_, line)) => {
+                let line = line.map_err(|err| format!("Cannot read recording: {}", err))?;
+                serde_json::from_str(&line)
+                    .map_err(|err| format!("Serde error (header): {}", err))?
+            }
+            None => return Err("Empty recording".to_string()),
+        };
+        let mut events = vec![];
+        for (index, line) in lines {
+            let line = line.map_err(|err| format!("Cannot read recording: {}", err))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let TimedEvent { time, event } = serde_json::from_str(&line)
+                .map_err(|err| format!("Serde error (line {}): {}", index + 1, err))?;
+            events.push((time, event));
+        }
+        Ok(Recording { header, events })
+    }
+
+    pub fn get_start(&self) -> SimTime {
+        self.header.time
+    }
+
+    pub fn get_end(&self) -> SimTime {
+        self.events
+            .last()
+            .map_or(self.header.time, |(time, _)| *time)
+    }
+}
+
+/// A recording played back, which can be moved to any point in time without re-simulating.
+pub struct Replay {
+    recording: Recording,
+    time: SimTime,
+    /// Index of the first event not applied yet.
+    next: usize,
+    nodes: BTreeMap<NodeId, RecordedNode>,
+    ethers: BTreeMap<EtherId, RecordedEther>,
+    loads: HashMap<EtherId, u64>,
+    transmissions: BTreeMap<u64, (SimTime, RecordedTransmission)>,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Replay {
+        let mut replay = Replay {
+            time: recording.get_start(),
+            recording,
+            next: 0,
+            nodes: BTreeMap::new(),
+            ethers: BTreeMap::new(),
+            loads: HashMap::new(),
+            transmissions: BTreeMap::new(),
+        };
+        replay.rewind();
+        replay.seek(replay.get_start());
+        replay
+    }
+
+    fn rewind(&mut self) {
+        let header = &self.recording.header;
+        self.time = header.time;
+        self.next = 0;
+        self.nodes = header
+            .nodes
+            .iter()
+            .map(|node| (node.id, node.clone()))
+            .collect();
+        self.ethers = header
+            .ethers
+            .iter()
+            .map(|ether| (ether.id, ether.clone()))
+            .collect();
+        self.loads.clear();
+        self.transmissions.clear();
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.recording.header.seed
+    }
+
+    pub fn get_time(&self) -> SimTime {
+        self.time
+    }
+
+    pub fn get_start(&self) -> SimTime {
+        self.recording.get_start()
+    }
+
+    pub fn get_end(&self) -> SimTime {
+        self.recording.get_end()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.get_end()
+    }
+
+    /// Moves to the given time, clamped to the recording.
+    pub fn seek(&mut self, time: SimTime) {
+        let time = time.clamp(self.get_start(), self.get_end());
+        if time < self.time {
+            self.rewind();
+        }
+        while let Some((event_time, event)) = self.recording.events.get(self.next) {
+            if *event_time > time {
+                break;
+            }
+            match event {
+                RecordedEvent::TransmissionStart(transmission) => {
+                    self.loads.insert(transmission.ether, transmission.load);
+                    self.transmissions
+                        .insert(transmission.id, (*event_time, transmission.clone()));
+                }
+                RecordedEvent::TransmissionEnd { id } => {
+                    self.transmissions.remove(id);
+                }
+                RecordedEvent::LinkState { ether, up } => {
+                    if let Some(ether) = self.ethers.get_mut(ether) {
+                        ether.up = *up;
+                    }
+                }
+                RecordedEvent::NodeState { node, up } => {
+                    if let Some(node) = self.nodes.get_mut(node) {
+                        node.up = *up;
+                    }
+                }
+                RecordedEvent::EtherRemoved { ether } => {
+                    self.ethers.remove(ether);
+                    self.loads.remove(ether);
+                }
+                RecordedEvent::NodeRemoved { node } => {
+                    self.nodes.remove(node);
+                }
+            }
+            self.next += 1;
+        }
+        self.time = time;
+    }
+
+    pub fn advance(&mut self, duration: SimTime) {
+        self.seek(self.time + duration);
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &RecordedNode> {
+        self.nodes.values()
+    }
+
+    pub fn ethers(&self) -> impl Iterator<Item = &RecordedEther> {
+        self.ethers.values()
+    }
+
+    pub fn get_load(&self, ether: EtherId) -> u64 {
+        self.loads.get(&ether).copied().unwrap_or(0)
+    }
+
+    /// Packets on the links along with where they are now.
+    pub fn transmissions(&self) -> impl Iterator<Item = (Point, &RecordedTransmission)> {
+        self.transmissions
+            .values()
+            .map(move |(started, transmission)| {
+                let position = position_along(
+                    (transmission.from, transmission.to),
+                    *started,
+                    transmission.duration,
+                    self.time,
+                );
+                (position, transmission)
+            })
+    }
+}
@@ -0,0 +1,352 @@
+use super::node::NodeId;
+use super::packet::Hop;
+use super::SimTime;
+use serde::Serialize;
+use std::fmt;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacketStatus {
+    InFlight,
+    Delivered,
+    Dropped,
+    /// Discarded by a receiver because it was damaged on a link.
+    Corrupted,
+    /// Ran out of TTL, usually in a routing loop.
+    Expired,
+}
+
+#[derive(Clone, Serialize)]
+pub struct PacketRecord {
+    pub uuid: Uuid,
+    pub source: NodeId,
+    pub destination: NodeId,
+    pub size: u64,
+    pub status: PacketStatus,
+    pub sent: SimTime,
+    pub finished: Option<SimTime>,
+    pub latency: Option<SimTime>,
+    pub hops: u32,
+    /// Hops on the shortest path between the packet's ends when it was sent, if there was one.
+    pub shortest_hops: Option<u32>,
+    pub path: Vec<Hop>,
+}
+
+impl PacketRecord {
+    pub fn new(
+        uuid: Uuid,
+        source: NodeId,
+        destination: NodeId,
+        size: u64,
+        sent: SimTime,
+    ) -> PacketRecord {
+        PacketRecord {
+            uuid,
+            source,
+            destination,
+            size,
+            status: PacketStatus::InFlight,
+            sent,
+            finished: None,
+            latency: None,
+            hops: 0,
+            shortest_hops: None,
+            path: vec![],
+        }
+    }
+
+    pub fn finish(&mut self, status: PacketStatus, time: SimTime) {
+        self.status = status;
+        self.finished = Some(time);
+        if status == PacketStatus::Delivered {
+            self.latency = Some(time - self.sent);
+        }
+    }
+}
+
+/// How the packets from one node to another fared.
+#[derive(Clone, Debug, Serialize)]
+pub struct FlowStatistics {
+    pub source: NodeId,
+    pub destination: NodeId,
+    pub sent: usize,
+    pub delivered: usize,
+    /// Dropped, corrupted or expired.
+    pub dropped: usize,
+    pub in_flight: usize,
+    pub min_latency: Option<SimTime>,
+    pub mean_latency: Option<f64>,
+    pub p95_latency: Option<SimTime>,
+    /// Over delivered packets.
+    pub mean_hops: Option<f64>,
+    /// Mean hops on the shortest path when each delivered packet was sent.
+    pub shortest_hops: Option<f64>,
+    /// Hops over shortest hops, for the delivered packets that had a shortest path.
+    pub stretch: Option<f64>,
+    /// Delivered bytes per second, from the first packet sent to the last one delivered.
+    pub throughput: Option<f64>,
+}
+
+impl FlowStatistics {
+    pub const CSV_HEADER: &'static str = "source,destination,sent,delivered,dropped,in_flight,\
+        min_latency,mean_latency,p95_latency,mean_hops,shortest_hops,stretch,throughput";
+
+    pub fn new(source: NodeId, destination: NodeId, packets: &[&PacketRecord]) -> FlowStatistics {
+        let delivered: Vec<&PacketRecord> = packets
+            .iter()
+            .filter(|packet| packet.status == PacketStatus::Delivered)
+            .copied()
+            .collect();
+        let in_flight = packets
+            .iter()
+            .filter(|packet| packet.status == PacketStatus::InFlight)
+            .count();
+
+        let mut latencies: Vec<SimTime> = delivered
+            .iter()
+            .filter_map(|packet| packet.latency)
+            .collect();
+        latencies.sort_unstable();
+        let count = latencies.len();
+        let mean = |sum: f64| (count > 0).then(|| sum / count as f64);
+        let mean_latency = mean(latencies.iter().sum::<SimTime>() as f64);
+        // Nearest rank.
+        let p95_latency = match count {
+            0 => None,
+            _ => Some(latencies[(count * 95).div_ceil(100) - 1]),
+        };
+        let mean_hops = mean(delivered.iter().map(|packet| packet.hops as f64).sum());
+        let compared: Vec<(u32, u32)> = delivered
+            .iter()
+            .filter_map(|packet| Some((packet.hops, packet.shortest_hops?)))
+            .collect();
+        let shortest: u32 = compared.iter().map(|(_, shortest)| shortest).sum();
+        let shortest_hops = (!compared.is_empty()).then(|| shortest as f64 / compared.len() as f64);
+        let stretch = (shortest > 0)
+            .then(|| compared.iter().map(|(hops, _)| hops).sum::<u32>() as f64 / shortest as f64);
+
+        let first_sent = packets.iter().map(|packet| packet.sent).min();
+        let last_delivered = delivered.iter().filter_map(|packet| packet.finished).max();
+        let throughput = match (first_sent, last_delivered) {
+            (Some(first), Some(last)) if last > first => {
+                let bytes: u64 = delivered.iter().map(|packet| packet.size).sum();
+                Some(bytes as f64 * 1000.0 / (last - first) as f64)
+            }
+            _ => None,
+        };
+
+        FlowStatistics {
+            source,
+            destination,
+            sent: packets.len(),
+            delivered: delivered.len(),
+            dropped: packets.len() - delivered.len() - in_flight,
+            in_flight,
+            min_latency: latencies.first().copied(),
+            mean_latency,
+            p95_latency,
+            mean_hops,
+            shortest_hops,
+            stretch,
+            throughput,
+        }
+    }
+
+    /// A line under `CSV_HEADER`, with empty fields for missing values.
+    pub fn to_csv(&self) -> String {
+        fn field<T: ToString>(value: Option<T>) -> String {
+            value.map_or(String::new(), |value| value.to_string())
+        }
+        let round = |value: Option<f64>| value.map(|value| format!("{:.3}", value));
+        [
+            self.source.to_string(),
+            self.destination.to_string(),
+            self.sent.to_string(),
+            self.delivered.to_string(),
+            self.dropped.to_string(),
+            self.in_flight.to_string(),
+            field(self.min_latency),
+            field(round(self.mean_latency)),
+            field(self.p95_latency),
+            field(round(self.mean_hops)),
+            field(round(self.shortest_hops)),
+            field(round(self.stretch)),
+            field(round(self.throughput)),
+        ]
+        .join(",")
+    }
+}
+
+pub fn flows_to_csv(flows: &[FlowStatistics]) -> String {
+    let mut csv = FlowStatistics::CSV_HEADER.to_string() + "\n";
+    for flow in flows {
+        csv += &flow.to_csv();
+        csv += "\n";
+    }
+    csv
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub seed: u64,
+    pub time: SimTime,
+    pub sent: usize,
+    pub delivered: usize,
+    pub dropped: usize,
+    pub corrupted: usize,
+    pub expired: usize,
+    pub in_flight: usize,
+    pub flows: Vec<FlowStatistics>,
+    pub packets: Vec<PacketRecord>,
+}
+
+impl Report {
+    pub fn new(
+        seed: u64,
+        time: SimTime,
+        packets: Vec<PacketRecord>,
+        flows: Vec<FlowStatistics>,
+    ) -> Report {
+        let count = |status| {
+            packets
+                .iter()
+                .filter(|packet| packet.status == status)
+                .count()
+        };
+        Report {
+            seed,
+            time,
+            sent: packets.len(),
+            delivered: count(PacketStatus::Delivered),
+            dropped: count(PacketStatus::Dropped),
+            corrupted: count(PacketStatus::Corrupted),
+            expired: count(PacketStatus::Expired),
+            in_flight: count(PacketStatus::InFlight),
+            flows,
+            packets,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| format!("Serde error: {}", err))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Seed: {}", self.seed)?;
+        writeln!(f, "Simulated time: {} ms", self.time)?;
+        writeln!(
+            f,
+            "Packets sent: {}, delivered: {}, dropped: {}, corrupted: {}, expired: {}, in flight: {}",
+            self.sent,
+            self.delivered,
+            self.dropped,
+            self.corrupted,
+            self.expired,
+            self.in_flight
+        )?;
+        for flow in self.flows.iter() {
+            let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            writeln!(
+                f,
+                "Flow {:3} > {:3} : sent {}, delivered {}, dropped {} | latency min {} mean {} p95 {} | hops {} (stretch {}) | {} B/s",
+                flow.source,
+                flow.destination,
+                flow.sent,
+                flow.delivered,
+                flow.dropped,
+                or_dash(flow.min_latency.map(|latency| latency.to_string())),
+                or_dash(flow.mean_latency.map(|latency| format!("{:.1}", latency))),
+                or_dash(flow.p95_latency.map(|latency| latency.to_string())),
+                or_dash(flow.mean_hops.map(|hops| format!("{:.1}", hops))),
+                or_dash(flow.stretch.map(|stretch| format!("{:.2}", stretch))),
+                or_dash(flow.throughput.map(|throughput| format!("{:.0}", throughput))),
+            )?;
+        }
+        for packet in self.packets.iter() {
+            let latency = match packet.latency {
+                Some(latency) => format!("{} ms", latency),
+                None => "-".to_string(),
+            };
+            let path: Vec<String> = packet.path.iter().map(|hop| hop.node.to_string()).collect();
+            writeln!(
+                f,
+                "{} {:3} > {:3} : {:9} | latency {:>8} | hops {:3} | path {}",
+                packet.uuid,
+                packet.source,
+                packet.destination,
+                format!("{:?}", packet.status).to_uppercase(),
+                latency,
+                packet.hops,
+                path.join(">"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        status: PacketStatus,
+        sent: SimTime,
+        finished: SimTime,
+        hops: u32,
+        shortest_hops: Option<u32>,
+    ) -> PacketRecord {
+        let mut record = PacketRecord::new(Uuid::new_v4(), 0, 1, 100, sent);
+        record.hops = hops;
+        record.shortest_hops = shortest_hops;
+        if status != PacketStatus::InFlight {
+            record.finish(status, finished);
+        }
+        record
+    }
+
+    #[test]
+    fn flow_statistics() {
+        let mut records: Vec<PacketRecord> = (1..=20)
+            .map(|latency| record(PacketStatus::Delivered, 0, latency, 2, Some(2)))
+            .collect();
+        records.push(record(PacketStatus::Delivered, 0, 20, 4, Some(3)));
+        records.push(record(PacketStatus::Dropped, 0, 5, 1, Some(2)));
+        records.push(record(PacketStatus::Expired, 0, 5, 64, Some(2)));
+        records.push(record(PacketStatus::InFlight, 10, 0, 1, Some(2)));
+        let packets: Vec<&PacketRecord> = records.iter().collect();
+        let flow = FlowStatistics::new(0, 1, &packets);
+
+        assert_eq!(flow.sent, 24);
+        assert_eq!(flow.delivered, 21);
+        assert_eq!(flow.dropped, 2);
+        assert_eq!(flow.in_flight, 1);
+        assert_eq!(flow.min_latency, Some(1));
+        assert_eq!(flow.mean_latency, Some(230.0 / 21.0));
+        assert_eq!(flow.p95_latency, Some(20));
+        assert_eq!(flow.mean_hops, Some(44.0 / 21.0));
+        // Only delivered packets count, each against its own shortest path.
+        assert_eq!(flow.shortest_hops, Some(43.0 / 21.0));
+        assert_eq!(flow.stretch, Some(44.0 / 43.0));
+        assert_eq!(flow.throughput, Some(21.0 * 100.0 * 1000.0 / 20.0));
+    }
+
+    #[test]
+    fn flow_statistics_without_deliveries() {
+        let records = [
+            record(PacketStatus::Dropped, 0, 5, 1, Some(2)),
+            record(PacketStatus::InFlight, 0, 0, 0, None),
+        ];
+        let packets: Vec<&PacketRecord> = records.iter().collect();
+        let flow = FlowStatistics::new(0, 1, &packets);
+        assert_eq!((flow.delivered, flow.dropped, flow.in_flight), (0, 1, 1));
+        assert_eq!(flow.mean_latency, None);
+        assert_eq!(flow.p95_latency, None);
+        assert_eq!(flow.shortest_hops, None);
+        assert_eq!(flow.stretch, None);
+        assert_eq!(flow.throughput, None);
+        assert_eq!(flow.to_csv(), "0,1,2,0,1,1,,,,,,,");
+    }
+}
@@ -0,0 +1,321 @@
+use super::{Cost, Route, RouterContext, RoutingProtocol};
+use crate::network::node::{NodeId, NodeInterfaceId};
+use crate::network::packet::Payload;
+use crate::network::SimTime;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DistanceVectorSettings {
+    /// Time between two periodic advertisements of the whole table.
+    pub update_interval: SimTime,
+    /// A route not refreshed for this long is considered unreachable.
+    pub route_timeout: SimTime,
+    /// An unreachable route is still advertised (as such) for this long before being forgotten.
+    pub garbage_collection: SimTime,
+    /// Don't advertise routes back through the interface they were learned from.
+    pub split_horizon: bool,
+    /// Advertise routes back through the interface they were learned from as unreachable.
+    pub poison_reverse: bool,
+    /// The metric meaning "unreachable".
+    pub infinity: u32,
+}
+
+impl Default for DistanceVectorSettings {
+    fn default() -> Self {
+        DistanceVectorSettings {
+            update_interval: 1000,
+            route_timeout: 6000,
+            garbage_collection: 4000,
+            split_horizon: true,
+            poison_reverse: false,
+            infinity: 16,
+        }
+    }
+}
+
+struct DistanceVectorRoute {
+    /// `None` for the router's own entry.
+    interface: Option<NodeInterfaceId>,
+    metric: u32,
+    updated: SimTime,
+}
+
+/// RIP-style routing: every router periodically tells its neighbors how many hops away
+/// it is from every destination it knows.
+pub struct DistanceVector {
+    settings: DistanceVectorSettings,
+    table: BTreeMap<NodeId, DistanceVectorRoute>,
+    next_update: SimTime,
+}
+
+impl DistanceVector {
+    pub fn new(settings: DistanceVectorSettings) -> DistanceVector {
+        DistanceVector {
+            settings,
+            table: BTreeMap::new(),
+            next_update: 0,
+        }
+    }
+
+    fn advertisement(&self, interface: &NodeInterfaceId) -> Payload {
+        let entries = self
+            .table
+            .iter()
+            .filter_map(|(destination, route)| {
+                if route.interface.as_ref() != Some(interface) {
+                    Some((*destination, route.metric))
+                } else if self.settings.poison_reverse {
+                    Some((*destination, self.settings.infinity))
+                } else if self.settings.split_horizon {
+                    None
+                } else {
+                    Some((*destination, route.metric))
+                }
+            })
+            .collect();
+        Payload::DistanceVector(entries)
+    }
+
+    fn expire(&mut self, interfaces: &[NodeInterfaceId], time: SimTime) {
+        let settings = &self.settings;
+        for route in self.table.values_mut() {
+            let expired = match &route.interface {
+                // Routes through an interface whose link went down are lost right away.
+                Some(interface) => {
+                    !interfaces.contains(interface)
+                        || time >= route.updated + settings.route_timeout
+                }
+                None => false,
+            };
+            if expired && route.metric < settings.infinity {
+                route.metric = settings.infinity;
+                route.updated = time;
+            }
+        }
+        self.table.retain(|_, route| {
+            route.metric < settings.infinity || time < route.updated + settings.garbage_collection
+        });
+    }
+
+    fn update_known_routes(&self, known_routes: &mut HashMap<NodeId, Vec<NodeInterfaceId>>) {
+        known_routes.clear();
+        for (destination, route) in self.table.iter() {
+            if let Some(interface) = &route.interface {
+                if route.metric < self.settings.infinity {
+                    known_routes.insert(*destination, vec![interface.clone()]);
+                }
+            }
+        }
+    }
+}
+
+impl RoutingProtocol for DistanceVector {
+    fn tick(&mut self, router: RouterContext, time: SimTime) -> Vec<(NodeInterfaceId, Payload)> {
+        self.table.entry(router.id).or_insert(DistanceVectorRoute {
+            interface: None,
+            metric: 0,
+            updated: time,
+        });
+        self.expire(router.interfaces, time);
+        self.update_known_routes(router.known_routes);
+
+        if time < self.next_update {
+            return vec![];
+        }
+        self.next_update = time + self.settings.update_interval;
+        router
+            .interfaces
+            .iter()
+            .map(|interface| (interface.clone(), self.advertisement(interface)))
+            .collect()
+    }
+
+    fn receive(
+        &mut self,
+        router: RouterContext,
+        interface: &NodeInterfaceId,
+        _sender: NodeId,
+        payload: &Payload,
+        time: SimTime,
+    ) -> Vec<(NodeInterfaceId, Payload)> {
+        let entries = match payload {
+            Payload::DistanceVector(entries) => entries,
+            _ => return vec![],
+        };
+        let infinity = self.settings.infinity;
+        for (destination, metric) in entries.iter() {
+            if *destination == router.id {
+                continue;
+            }
+            let metric = metric.saturating_add(1).min(infinity);
+            match self.table.get_mut(destination) {
+                Some(route) if route.interface.as_ref() == Some(interface) => {
+                    // The neighbor we route through is authoritative, even when things got worse.
+                    if metric < infinity || route.metric < infinity {
+                        route.updated = time;
+                    }
+                    route.metric = metric;
+                }
+                Some(route) if metric < route.metric => {
+                    route.interface = Some(interface.clone());
+                    route.metric = metric;
+                    route.updated = time;
+                }
+                None if metric < infinity => {
+                    self.table.insert(
+                        *destination,
+                        DistanceVectorRoute {
+                            interface: Some(interface.clone()),
+                            metric,
+                            updated: time,
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+        self.update_known_routes(router.known_routes);
+        vec![]
+    }
+
+    fn routes(&self) -> Vec<Route> {
+        self.table
+            .iter()
+            .filter_map(|(destination, route)| {
+                route.interface.as_ref().map(|interface| Route {
+                    destination: *destination,
+                    interfaces: vec![interface.clone()],
+                    metric: Some(route.metric as Cost),
+                    as_path: vec![],
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::routing::testing::TestNetwork;
+
+    fn settings(split_horizon: bool, poison_reverse: bool) -> DistanceVectorSettings {
+        DistanceVectorSettings {
+            update_interval: 10,
+            route_timeout: 55,
+            garbage_collection: 40,
+            split_horizon,
+            poison_reverse,
+            ..DistanceVectorSettings::default()
+        }
+    }
+
+    /// Routers 0, 1 and 2 in a line.
+    fn line(settings: DistanceVectorSettings) -> TestNetwork<DistanceVector> {
+        TestNetwork::new(3, &[(0, 1), (1, 2)], || {
+            DistanceVector::new(settings.clone())
+        })
+    }
+
+    fn metric(
+        network: &TestNetwork<DistanceVector>,
+        router: NodeId,
+        destination: NodeId,
+    ) -> Option<u32> {
+        network.routers[router]
+            .protocol
+            .table
+            .get(&destination)
+            .map(|route| route.metric)
+    }
+
+    #[test]
+    fn routes_converge_along_the_line() {
+        let mut network = line(settings(true, false));
+        for time in 0..=10 {
+            network.step(time);
+        }
+        assert_eq!(metric(&network, 0, 2), Some(2));
+        assert_eq!(metric(&network, 2, 0), Some(2));
+        assert_eq!(network.routers[0].known_routes[&2], ["0-1"]);
+        assert_eq!(network.routers[1].known_routes[&2], ["1-2"]);
+        let routes: Vec<(NodeId, Option<Cost>)> = network.routers[0]
+            .protocol
+            .routes()
+            .iter()
+            .map(|route| (route.destination, route.metric))
+            .collect();
+        assert_eq!(routes, [(1, Some(1)), (2, Some(2))]);
+    }
+
+    #[test]
+    fn split_horizon_and_poison_reverse() {
+        let advertised = |settings: DistanceVectorSettings, interface: &str| {
+            let mut network = line(settings);
+            for time in 0..=10 {
+                network.step(time);
+            }
+            match network.routers[1]
+                .protocol
+                .advertisement(&interface.to_string())
+            {
+                Payload::DistanceVector(entries) => entries,
+                _ => panic!("not a distance vector"),
+            }
+        };
+        // Router 1 learned about 2 through `1-2`, so it doesn't tell 2 about it...
+        assert_eq!(advertised(settings(true, false), "1-2"), [(0, 1), (1, 0)]);
+        assert_eq!(advertised(settings(true, false), "1-0"), [(1, 0), (2, 1)]);
+        // ...or tells it that it can't get there.
+        assert_eq!(
+            advertised(settings(true, true), "1-2"),
+            [(0, 1), (1, 0), (2, 16)]
+        );
+        assert_eq!(
+            advertised(settings(false, false), "1-2"),
+            [(0, 1), (1, 0), (2, 1)]
+        );
+    }
+
+    /// Cuts the link between routers 1 and 2 between two updates, and tells how long it
+    /// takes router 0 to give up on 2, along with the worst metric it believed in meanwhile.
+    fn give_up_on_cut_destination(settings: DistanceVectorSettings) -> (SimTime, u32) {
+        let infinity = settings.infinity;
+        let mut network = line(settings);
+        for time in 0..25 {
+            network.step(time);
+        }
+        network.cut(1, 2);
+        let mut worst = 0;
+        for time in 25..1000 {
+            network.step(time);
+            let metric = metric(&network, 0, 2).unwrap_or(infinity);
+            if metric >= infinity {
+                assert!(!network.routers[0].known_routes.contains_key(&2));
+                return (time - 25, worst);
+            }
+            worst = worst.max(metric);
+        }
+        panic!("router 0 never gave up on 2");
+    }
+
+    #[test]
+    fn count_to_infinity() {
+        // With split horizon or poison reverse, router 0 gives up as soon as router 1
+        // advertises that it lost its route.
+        let (elapsed, worst) = give_up_on_cut_destination(settings(true, false));
+        assert_eq!(worst, 2);
+        assert_eq!(
+            give_up_on_cut_destination(settings(false, true)),
+            (elapsed, 2)
+        );
+
+        // Without, router 0 tells router 1 about its route to 2 before router 1 advertises
+        // it lost it; they end up pointing at each other, counting up until the metric
+        // reaches infinity.
+        let (counting, worst) = give_up_on_cut_destination(settings(false, false));
+        assert_eq!(worst, 14);
+        assert!(counting > elapsed + 50);
+    }
+}
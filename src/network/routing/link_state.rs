@@ -0,0 +1,362 @@
+use super::{Cost, Graph, Route, RouterContext, RoutingProtocol};
+use crate::network::node::{NodeId, NodeInterfaceId};
+use crate::network::packet::Payload;
+use crate::network::SimTime;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkStateSettings {
+    /// Time between two hellos sent to discover neighbors and measure the link costs.
+    pub hello_interval: SimTime,
+    /// A neighbor not heard from for this long is considered gone.
+    pub dead_interval: SimTime,
+    /// Time after which a router floods its advertisement again even if nothing changed.
+    pub refresh_interval: SimTime,
+    /// Advertisements not refreshed for this long are removed from the database.
+    pub max_age: SimTime,
+}
+
+impl Default for LinkStateSettings {
+    fn default() -> Self {
+        LinkStateSettings {
+            hello_interval: 1000,
+            dead_interval: 4000,
+            refresh_interval: 10000,
+            max_age: 30000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkStateAdvertisement {
+    pub origin: NodeId,
+    pub sequence: u64,
+    /// `(neighbor, cost)` pairs of the origin.
+    pub links: Vec<(NodeId, Cost)>,
+}
+
+struct Neighbor {
+    interface: NodeInterfaceId,
+    cost: Cost,
+    last_seen: SimTime,
+}
+
+/// OSPF-style routing: routers flood the list of their neighbors through the whole network,
+/// and each one runs SPF over the resulting topology database.
+pub struct LinkState {
+    settings: LinkStateSettings,
+    neighbors: BTreeMap<NodeId, Neighbor>,
+    /// Advertisements by origin, along with the time they were received.
+    database: BTreeMap<NodeId, (LinkStateAdvertisement, SimTime)>,
+    sequence: u64,
+    next_hello: SimTime,
+    next_refresh: SimTime,
+    routes: Vec<Route>,
+}
+
+impl LinkState {
+    pub fn new(settings: LinkStateSettings) -> LinkState {
+        LinkState {
+            settings,
+            neighbors: BTreeMap::new(),
+            database: BTreeMap::new(),
+            sequence: 0,
+            next_hello: 0,
+            next_refresh: 0,
+            routes: vec![],
+        }
+    }
+
+    fn originate(
+        &mut self,
+        router: &RouterContext,
+        time: SimTime,
+    ) -> Vec<(NodeInterfaceId, Payload)> {
+        self.sequence += 1;
+        self.next_refresh = time + self.settings.refresh_interval;
+        let advertisement = LinkStateAdvertisement {
+            origin: router.id,
+            sequence: self.sequence,
+            links: self
+                .neighbors
+                .iter()
+                .map(|(neighbor, link)| (*neighbor, link.cost))
+                .collect(),
+        };
+        self.database
+            .insert(router.id, (advertisement.clone(), time));
+        router
+            .interfaces
+            .iter()
+            .map(|interface| (interface.clone(), Payload::LinkState(advertisement.clone())))
+            .collect()
+    }
+
+    fn run_spf(&mut self, router: RouterContext) {
+        let mut graph = Graph::new();
+        for (origin, (advertisement, _)) in self.database.iter() {
+            graph.add_node(*origin);
+            for (neighbor, cost) in advertisement.links.iter() {
+                // Only links both ends agree on are usable.
+                let confirmed = self.database.get(neighbor).is_some_and(|(reverse, _)| {
+                    reverse.links.iter().any(|(peer, _)| peer == origin)
+                });
+                if confirmed {
+                    graph.add_edge(*origin, *neighbor, *cost);
+                }
+            }
+        }
+
+        let tree = graph.shortest_path_tree(router.id);
+        router.known_routes.clear();
+        self.routes.clear();
+        for destination in self.database.keys() {
+            let interfaces: Vec<NodeInterfaceId> = tree
+                .first_hops(*destination)
+                .iter()
+                .filter_map(|neighbor| self.neighbors.get(neighbor))
+                .map(|link| link.interface.clone())
+                .collect();
+            if interfaces.is_empty() {
+                continue;
+            }
+            router.known_routes.insert(*destination, interfaces.clone());
+            self.routes.push(Route {
+                destination: *destination,
+                interfaces,
+                metric: tree.distance(*destination),
+                as_path: vec![],
+            });
+        }
+    }
+}
+
+impl RoutingProtocol for LinkState {
+    fn tick(&mut self, router: RouterContext, time: SimTime) -> Vec<(NodeInterfaceId, Payload)> {
+        let mut sent = vec![];
+        if time >= self.next_hello {
+            self.next_hello = time + self.settings.hello_interval;
+            sent.extend(
+                router
+                    .interfaces
+                    .iter()
+                    .map(|interface| (interface.clone(), Payload::Hello { sent: time })),
+            );
+        }
+
+        let neighbors_count = self.neighbors.len();
+        let dead_interval = self.settings.dead_interval;
+        self.neighbors.retain(|_, neighbor| {
+            time < neighbor.last_seen + dead_interval
+                && router.interfaces.contains(&neighbor.interface)
+        });
+        let neighbors_changed = self.neighbors.len() != neighbors_count;
+
+        let database_size = self.database.len();
+        let (id, max_age) = (router.id, self.settings.max_age);
+        self.database
+            .retain(|origin, (_, received)| *origin == id || time < *received + max_age);
+        let database_changed = self.database.len() != database_size;
+
+        if neighbors_changed || time >= self.next_refresh {
+            sent.extend(self.originate(&router, time));
+        }
+        if neighbors_changed || database_changed {
+            self.run_spf(router);
+        }
+        sent
+    }
+
+    fn receive(
+        &mut self,
+        router: RouterContext,
+        interface: &NodeInterfaceId,
+        sender: NodeId,
+        payload: &Payload,
+        time: SimTime,
+    ) -> Vec<(NodeInterfaceId, Payload)> {
+        match payload {
+            Payload::Hello { sent } => {
+                let cost = time.saturating_sub(*sent).max(1) as Cost;
+                let changed = self.neighbors.get(&sender).is_none_or(|neighbor| {
+                    neighbor.cost != cost || neighbor.interface != *interface
+                });
+                self.neighbors.insert(
+                    sender,
+                    Neighbor {
+                        interface: interface.clone(),
+                        cost,
+                        last_seen: time,
+                    },
+                );
+                if changed {
+                    let sent = self.originate(&router, time);
+                    self.run_spf(router);
+                    sent
+                } else {
+                    vec![]
+                }
+            }
+            Payload::LinkState(advertisement) => {
+                if advertisement.origin == router.id {
+                    // A stale copy of our own advertisement from before a restart.
+                    if advertisement.sequence > self.sequence {
+                        self.sequence = advertisement.sequence;
+                        let sent = self.originate(&router, time);
+                        self.run_spf(router);
+                        return sent;
+                    }
+                    return vec![];
+                }
+                match self.database.get(&advertisement.origin) {
+                    // Most likely the origin restarted and counts from scratch again; the
+                    // newer copy tells it where to carry on from.
+                    Some((known, _)) if advertisement.sequence < known.sequence => {
+                        return vec![(interface.clone(), Payload::LinkState(known.clone()))];
+                    }
+                    Some((known, _)) if advertisement.sequence == known.sequence => {
+                        return vec![];
+                    }
+                    _ => {}
+                }
+                self.database
+                    .insert(advertisement.origin, (advertisement.clone(), time));
+                let sent = router
+                    .interfaces
+                    .iter()
+                    .filter(|other| *other != interface)
+                    .map(|other| (other.clone(), payload.clone()))
+                    .collect();
+                self.run_spf(router);
+                sent
+            }
+            _ => vec![],
+        }
+    }
+
+    fn routes(&self) -> Vec<Route> {
+        self.routes.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::routing::testing::TestNetwork;
+
+    /// Routers 0 to 3 in a ring.
+    fn ring() -> TestNetwork<LinkState> {
+        TestNetwork::new(4, &[(0, 1), (1, 2), (2, 3), (3, 0)], || {
+            LinkState::new(LinkStateSettings::default())
+        })
+    }
+
+    fn metric(
+        network: &TestNetwork<LinkState>,
+        router: NodeId,
+        destination: NodeId,
+    ) -> Option<Cost> {
+        network.routers[router]
+            .protocol
+            .routes()
+            .iter()
+            .find(|route| route.destination == destination)
+            .and_then(|route| route.metric)
+    }
+
+    #[test]
+    fn routes_converge_and_recover_from_a_link_failure() {
+        let mut network = ring();
+        for time in 0..10 {
+            network.step(time);
+        }
+        assert_eq!(metric(&network, 0, 1), Some(1));
+        assert_eq!(metric(&network, 0, 2), Some(2));
+        // Both ways around the ring to the opposite router cost the same.
+        let mut interfaces = network.routers[0].known_routes[&2].clone();
+        interfaces.sort();
+        assert_eq!(interfaces, ["0-1", "0-3"]);
+
+        network.cut(0, 1);
+        network.step(10);
+
+        // Neither end has to wait for the dead interval when its interface goes down.
+        assert_eq!(metric(&network, 0, 1), Some(3));
+        assert_eq!(network.routers[0].known_routes[&1], ["0-3"]);
+        assert_eq!(network.routers[0].known_routes[&2], ["0-3"]);
+        assert_eq!(metric(&network, 1, 0), Some(3));
+        assert_eq!(network.routers[1].known_routes[&0], ["1-2"]);
+        // The others hear about it through the flooded advertisements.
+        assert_eq!(network.routers[2].known_routes[&0], ["2-3"]);
+        assert_eq!(network.routers[3].known_routes[&1], ["3-2"]);
+    }
+
+    #[test]
+    fn advertisements_of_silent_routers_age_out() {
+        let mut network = ring();
+        for time in 0..10 {
+            network.step(time);
+        }
+        network.cut(2, 3);
+        network.cut(3, 0);
+        let max_age = LinkStateSettings::default().max_age;
+        for time in 10..max_age {
+            network.step(time);
+        }
+        // Nobody confirms router 3's links anymore, but its advertisement is still around.
+        let database = &network.routers[0].protocol.database;
+        assert!(database.contains_key(&3));
+        assert!(metric(&network, 0, 3).is_none());
+
+        for time in max_age..max_age + 20 {
+            network.step(time);
+        }
+        // The others keep refreshing theirs.
+        let database = &network.routers[0].protocol.database;
+        assert_eq!(database.keys().copied().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(metric(&network, 0, 2), Some(2));
+    }
+
+    #[test]
+    fn restarted_routers_carry_on_from_their_last_sequence() {
+        let mut network = ring();
+        let restart = 2 * LinkStateSettings::default().refresh_interval + 10;
+        for time in 0..restart {
+            network.step(time);
+        }
+        let sequence = |network: &TestNetwork<LinkState>, router: NodeId, origin: NodeId| {
+            network.routers[router].protocol.database[&origin]
+                .0
+                .sequence
+        };
+        let before = sequence(&network, 3, 1);
+        assert!(before > 1);
+
+        // A restarted router knows nothing, its sequence included.
+        network.routers[1].protocol = LinkState::new(LinkStateSettings::default());
+        network.step(restart);
+        // Its neighbors send back their newer copy of its advertisement, so it doesn't take
+        // until those age out for the others to hear from it again.
+        let after = network.routers[1].protocol.sequence;
+        assert!(after > before);
+        assert_eq!(sequence(&network, 3, 1), after);
+
+        for time in restart + 1..restart + LinkStateSettings::default().hello_interval {
+            network.step(time);
+        }
+        assert_eq!(metric(&network, 3, 1), Some(2));
+        assert_eq!(
+            sequence(&network, 3, 1),
+            network.routers[1].protocol.sequence
+        );
+
+        // The restarted router itself learns about the others as they refresh.
+        let refreshed = restart + LinkStateSettings::default().refresh_interval;
+        for time in restart + LinkStateSettings::default().hello_interval..refreshed {
+            network.step(time);
+        }
+        assert_eq!(metric(&network, 1, 3), Some(2));
+    }
+}
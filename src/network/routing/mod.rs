@@ -0,0 +1,410 @@
+pub mod distance_vector;
+pub mod link_state;
+pub mod path_vector;
+
+use super::node::{AsNumber, NodeId, NodeInterfaceId};
+use super::packet::Payload;
+use super::SimTime;
+use distance_vector::{DistanceVector, DistanceVectorSettings};
+use link_state::{LinkState, LinkStateSettings};
+use path_vector::{PathVector, PathVectorSettings};
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+pub type Cost = i64;
+
+/// How routers learn their routes.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "protocol", rename_all = "snake_case")]
+pub enum Routing {
+    /// Routes are computed by `Network::calculate_routes` from the whole topology.
+    #[default]
+    Global,
+    DistanceVector(DistanceVectorSettings),
+    LinkState(LinkStateSettings),
+    PathVector(PathVectorSettings),
+}
+
+impl Routing {
+    pub fn create_protocol(&self) -> Option<Box<dyn RoutingProtocol>> {
+        match self {
+            Routing::Global => None,
+            Routing::DistanceVector(settings) => {
+                Some(Box::new(DistanceVector::new(settings.clone())))
+            }
+            Routing::LinkState(settings) => Some(Box::new(LinkState::new(settings.clone()))),
+            Routing::PathVector(settings) => Some(Box::new(PathVector::new(settings.clone()))),
+        }
+    }
+}
+
+/// The parts of a router a routing protocol may look at and change.
+pub struct RouterContext<'a> {
+    pub id: NodeId,
+    pub autonomous_system: Option<AsNumber>,
+    /// Connected interfaces, sorted so that protocols behave deterministically.
+    pub interfaces: &'a [NodeInterfaceId],
+    /// Every interface a destination is reachable through at the lowest cost.
+    pub known_routes: &'a mut HashMap<NodeId, Vec<NodeInterfaceId>>,
+}
+
+#[derive(Clone)]
+pub struct Route {
+    pub destination: NodeId,
+    pub interfaces: Vec<NodeInterfaceId>,
+    pub metric: Option<Cost>,
+    /// Autonomous systems on the way, for protocols that know them.
+    pub as_path: Vec<AsNumber>,
+}
+
+pub trait RoutingProtocol {
+    /// Called every simulation step; returns control payloads to send out of the given interfaces.
+    fn tick(&mut self, router: RouterContext, time: SimTime) -> Vec<(NodeInterfaceId, Payload)>;
+    fn receive(
+        &mut self,
+        router: RouterContext,
+        interface: &NodeInterfaceId,
+        sender: NodeId,
+        payload: &Payload,
+        time: SimTime,
+    ) -> Vec<(NodeInterfaceId, Payload)>;
+    fn routes(&self) -> Vec<Route>;
+}
+
+/// Adjacency lists indexed by `NodeId`; node IDs are slab keys, so they are dense.
+#[derive(Default)]
+pub struct Graph {
+    adjacency: Vec<Option<Vec<(NodeId, Cost)>>>,
+}
+
+impl Graph {
+    pub fn new() -> Graph {
+        Graph { adjacency: vec![] }
+    }
+
+    pub fn add_node(&mut self, node: NodeId) {
+        if self.adjacency.len() <= node {
+            self.adjacency.resize(node + 1, None);
+        }
+        self.adjacency[node].get_or_insert_with(Vec::new);
+    }
+
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId, cost: Cost) {
+        self.add_node(to);
+        self.add_node(from);
+        if let Some(edges) = self.adjacency[from].as_mut() {
+            edges.push((to, cost));
+        }
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.adjacency
+            .iter()
+            .enumerate()
+            .filter_map(|(node, edges)| edges.as_ref().map(|_| node))
+    }
+
+    pub fn neighbors(&self, node: NodeId) -> &[(NodeId, Cost)] {
+        match self.adjacency.get(node) {
+            Some(Some(edges)) => edges,
+            _ => &[],
+        }
+    }
+
+    pub fn shortest_path_tree(&self, source: NodeId) -> ShortestPathTree {
+        let size = self.adjacency.len().max(source + 1);
+        let mut tree = ShortestPathTree {
+            source,
+            distances: vec![None; size],
+            previous: vec![None; size],
+            first_hops: vec![vec![]; size],
+        };
+        let mut queue = BinaryHeap::new();
+        tree.distances[source] = Some(0);
+        queue.push(Reverse((0, source)));
+
+        while let Some(Reverse((distance, current))) = queue.pop() {
+            if tree.distances[current].is_some_and(|known| distance > known) {
+                continue;
+            }
+            for (next, cost) in self.neighbors(current) {
+                let distance_to_next = distance + cost;
+                if tree.distances[*next].is_none_or(|known| distance_to_next < known) {
+                    tree.distances[*next] = Some(distance_to_next);
+                    tree.previous[*next] = Some(current);
+                    tree.first_hops[*next] = if current == source {
+                        vec![*next]
+                    } else {
+                        tree.first_hops[current].clone()
+                    };
+                    queue.push(Reverse((distance_to_next, *next)));
+                } else if tree.distances[*next] == Some(distance_to_next) {
+                    // An equal-cost path: keep its first hops as well.
+                    let first_hops = if current == source {
+                        vec![*next]
+                    } else {
+                        tree.first_hops[current].clone()
+                    };
+                    let mut added = false;
+                    for first_hop in first_hops {
+                        if !tree.first_hops[*next].contains(&first_hop) {
+                            tree.first_hops[*next].push(first_hop);
+                            added = true;
+                        }
+                    }
+                    // Over zero-cost edges `next` may have been settled already; it has to
+                    // pass the new first hops on.
+                    if added {
+                        queue.push(Reverse((distance_to_next, *next)));
+                    }
+                }
+            }
+        }
+
+        tree
+    }
+}
+
+pub struct ShortestPathTree {
+    source: NodeId,
+    distances: Vec<Option<Cost>>,
+    previous: Vec<Option<NodeId>>,
+    first_hops: Vec<Vec<NodeId>>,
+}
+
+impl ShortestPathTree {
+    pub fn get_source(&self) -> NodeId {
+        self.source
+    }
+
+    pub fn distance(&self, destination: NodeId) -> Option<Cost> {
+        self.distances.get(destination).copied().flatten()
+    }
+
+    pub fn first_hop(&self, destination: NodeId) -> Option<NodeId> {
+        self.first_hops(destination).first().copied()
+    }
+
+    /// Neighbors of the source starting any of the shortest paths to `destination`.
+    pub fn first_hops(&self, destination: NodeId) -> &[NodeId] {
+        self.first_hops
+            .get(destination)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn path(&self, destination: NodeId) -> Option<Vec<NodeId>> {
+        self.distance(destination)?;
+        let mut path = vec![destination];
+        let mut current = destination;
+        while let Some(previous) = self.previous[current] {
+            path.push(previous);
+            current = previous;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Routers wired to each other directly, to test routing protocols without a `Network`.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::collections::VecDeque;
+
+    pub struct TestRouter<P> {
+        pub id: NodeId,
+        pub autonomous_system: Option<AsNumber>,
+        pub interfaces: Vec<NodeInterfaceId>,
+        pub protocol: P,
+        pub known_routes: HashMap<NodeId, Vec<NodeInterfaceId>>,
+    }
+
+    impl<P: RoutingProtocol> TestRouter<P> {
+        pub fn route(&self, destination: NodeId) -> Option<Route> {
+            self.protocol
+                .routes()
+                .into_iter()
+                .find(|route| route.destination == destination)
+        }
+    }
+
+    /// Each interface is named `<router>-<peer>`.
+    pub struct TestNetwork<P> {
+        pub routers: Vec<TestRouter<P>>,
+        peers: HashMap<NodeInterfaceId, (NodeId, NodeInterfaceId)>,
+    }
+
+    impl<P: RoutingProtocol> TestNetwork<P> {
+        pub fn new(count: usize, links: &[(NodeId, NodeId)], protocol: impl Fn() -> P) -> Self {
+            let mut network = TestNetwork {
+                routers: (0..count)
+                    .map(|id| TestRouter {
+                        id,
+                        autonomous_system: None,
+                        interfaces: vec![],
+                        protocol: protocol(),
+                        known_routes: HashMap::new(),
+                    })
+                    .collect(),
+                peers: HashMap::new(),
+            };
+            for (first, second) in links {
+                let (interface1, interface2) = (
+                    Self::interface(*first, *second),
+                    Self::interface(*second, *first),
+                );
+                network.routers[*first].interfaces.push(interface1.clone());
+                network.routers[*second].interfaces.push(interface2.clone());
+                network
+                    .peers
+                    .insert(interface1.clone(), (*second, interface2.clone()));
+                network.peers.insert(interface2, (*first, interface1));
+            }
+            for router in network.routers.iter_mut() {
+                router.interfaces.sort();
+            }
+            network
+        }
+
+        pub fn interface(router: NodeId, peer: NodeId) -> NodeInterfaceId {
+            format!("{}-{}", router, peer)
+        }
+
+        /// Takes the link between two routers down at both ends.
+        pub fn cut(&mut self, first: NodeId, second: NodeId) {
+            for (router, peer) in [(first, second), (second, first)] {
+                let interface = Self::interface(router, peer);
+                self.routers[router]
+                    .interfaces
+                    .retain(|other| *other != interface);
+            }
+        }
+
+        /// Ticks every router in order, handing what each one sends to the routers at the other
+        /// ends right away, along with whatever that makes them send in turn.
+        pub fn step(&mut self, time: SimTime) {
+            for index in 0..self.routers.len() {
+                let router = &mut self.routers[index];
+                let context = RouterContext {
+                    id: router.id,
+                    autonomous_system: router.autonomous_system,
+                    interfaces: &router.interfaces,
+                    known_routes: &mut router.known_routes,
+                };
+                let mut pending: VecDeque<_> = router
+                    .protocol
+                    .tick(context, time)
+                    .into_iter()
+                    .map(|(interface, payload)| (index, interface, payload))
+                    .collect();
+                while let Some((sender, interface, payload)) = pending.pop_front() {
+                    let (peer, peer_interface) = self.peers[&interface].clone();
+                    let router = &mut self.routers[peer];
+                    if !router.interfaces.contains(&peer_interface) {
+                        continue;
+                    }
+                    let context = RouterContext {
+                        id: router.id,
+                        autonomous_system: router.autonomous_system,
+                        interfaces: &router.interfaces,
+                        known_routes: &mut router.known_routes,
+                    };
+                    let sent =
+                        router
+                            .protocol
+                            .receive(context, &peer_interface, sender, &payload, time);
+                    pending.extend(
+                        sent.into_iter()
+                            .map(|(interface, payload)| (peer, interface, payload)),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(NodeId, NodeId, Cost)]) -> Graph {
+        let mut graph = Graph::new();
+        for (from, to, cost) in edges {
+            graph.add_edge(*from, *to, *cost);
+            graph.add_edge(*to, *from, *cost);
+        }
+        graph
+    }
+
+    #[test]
+    fn shortest_path_tree() {
+        // 0 - 1 - 2 is cheaper than 0 - 2 directly; 4 is on its own.
+        let mut graph = graph(&[(0, 1, 1), (1, 2, 1), (0, 2, 5), (2, 3, 2)]);
+        graph.add_node(4);
+        let tree = graph.shortest_path_tree(0);
+
+        assert_eq!(tree.get_source(), 0);
+        assert_eq!(tree.distance(0), Some(0));
+        assert_eq!(tree.distance(2), Some(2));
+        assert_eq!(tree.distance(3), Some(4));
+        assert_eq!(tree.path(3), Some(vec![0, 1, 2, 3]));
+        assert_eq!(tree.first_hop(3), Some(1));
+
+        assert_eq!(tree.distance(4), None);
+        assert_eq!(tree.path(4), None);
+        assert_eq!(tree.first_hop(4), None);
+        // Nodes the graph never heard of are unreachable too.
+        assert_eq!(tree.distance(10), None);
+        assert_eq!(tree.first_hop(10), None);
+    }
+
+    #[test]
+    fn equal_cost_paths_keep_every_first_hop() {
+        // Two ways around a square to 3, and a longer one through 4.
+        let graph = graph(&[
+            (0, 1, 1),
+            (1, 3, 1),
+            (0, 2, 1),
+            (2, 3, 1),
+            (0, 4, 1),
+            (4, 3, 2),
+            (3, 5, 1),
+        ]);
+        let tree = graph.shortest_path_tree(0);
+
+        let mut first_hops = tree.first_hops(3).to_vec();
+        first_hops.sort_unstable();
+        assert_eq!(first_hops, [1, 2]);
+        // Past the point where the paths join, both first hops still lead there.
+        let mut first_hops = tree.first_hops(5).to_vec();
+        first_hops.sort_unstable();
+        assert_eq!(first_hops, [1, 2]);
+        assert_eq!(tree.first_hops(1), [1]);
+        assert_eq!(tree.distance(5), Some(3));
+        assert!(tree.first_hops(10).is_empty());
+    }
+
+    #[test]
+    fn zero_cost_edges_keep_every_first_hop() {
+        // 3 and 4 are as far as each other, so 3 is settled before 4 brings it the first
+        // hop through 2.
+        let graph = graph(&[
+            (0, 1, 1),
+            (0, 2, 1),
+            (1, 3, 1),
+            (2, 4, 1),
+            (3, 4, 0),
+            (3, 5, 1),
+        ]);
+        let tree = graph.shortest_path_tree(0);
+
+        for destination in [3, 4, 5] {
+            let mut first_hops = tree.first_hops(destination).to_vec();
+            first_hops.sort_unstable();
+            assert_eq!(first_hops, [1, 2]);
+        }
+        assert_eq!(tree.distance(5), Some(3));
+    }
+}
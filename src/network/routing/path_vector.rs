@@ -0,0 +1,370 @@
+use super::{Cost, Route, RouterContext, RoutingProtocol};
+use crate::network::node::{AsNumber, NodeId, NodeInterfaceId};
+use crate::network::packet::Payload;
+use crate::network::SimTime;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// What a neighboring autonomous system is to us.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relationship {
+    Customer,
+    Peer,
+    Provider,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathVectorSettings {
+    /// Time between two periodic advertisements of the selected routes.
+    pub update_interval: SimTime,
+    /// Routes from a neighbor not heard from for this long are withdrawn.
+    pub route_timeout: SimTime,
+    /// `(us, them) -> what they are to us`, filled in from the scenario's autonomous systems.
+    #[serde(skip)]
+    pub relationships: HashMap<(AsNumber, AsNumber), Relationship>,
+}
+
+impl Default for PathVectorSettings {
+    fn default() -> Self {
+        PathVectorSettings {
+            update_interval: 1000,
+            route_timeout: 4000,
+            relationships: HashMap::new(),
+        }
+    }
+}
+
+impl PathVectorSettings {
+    pub fn add_provider(&mut self, customer: AsNumber, provider: AsNumber) {
+        self.relationships
+            .insert((customer, provider), Relationship::Provider);
+        self.relationships
+            .insert((provider, customer), Relationship::Customer);
+    }
+
+    pub fn add_peers(&mut self, first: AsNumber, second: AsNumber) {
+        self.relationships
+            .insert((first, second), Relationship::Peer);
+        self.relationships
+            .insert((second, first), Relationship::Peer);
+    }
+}
+
+/// Where a route was learned from, most preferred first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RouteOrigin {
+    /// The destination is in our own autonomous system.
+    Local,
+    Customer,
+    Peer,
+    Provider,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathVectorRoute {
+    pub destination: NodeId,
+    /// Autonomous systems crossed so far, nearest first.
+    pub as_path: Vec<AsNumber>,
+    /// Nodes crossed so far, nearest first; guards against loops inside an autonomous system.
+    pub node_path: Vec<NodeId>,
+    pub origin: RouteOrigin,
+}
+
+struct Neighbor {
+    autonomous_system: Option<AsNumber>,
+    routes: Vec<PathVectorRoute>,
+    updated: SimTime,
+}
+
+/// BGP-style routing: routers advertise whole paths, which lets them honor the
+/// customer/peer/provider policies between autonomous systems.
+pub struct PathVector {
+    settings: PathVectorSettings,
+    neighbors: BTreeMap<NodeInterfaceId, Neighbor>,
+    selected: BTreeMap<NodeId, (Option<NodeInterfaceId>, PathVectorRoute)>,
+    next_update: SimTime,
+}
+
+impl PathVector {
+    pub fn new(settings: PathVectorSettings) -> PathVector {
+        PathVector {
+            settings,
+            neighbors: BTreeMap::new(),
+            selected: BTreeMap::new(),
+            next_update: 0,
+        }
+    }
+
+    /// `None` for neighbors in our own autonomous system. Scenarios put every node in one if
+    /// any is defined, but nodes added by hand are in none; they are taken for peers.
+    fn relationship(
+        &self,
+        ours: Option<AsNumber>,
+        theirs: Option<AsNumber>,
+    ) -> Option<Relationship> {
+        match (ours, theirs) {
+            _ if ours == theirs => None,
+            (Some(ours), Some(theirs)) => Some(
+                self.settings
+                    .relationships
+                    .get(&(ours, theirs))
+                    .copied()
+                    .unwrap_or(Relationship::Peer),
+            ),
+            _ => Some(Relationship::Peer),
+        }
+    }
+
+    fn select(&mut self, id: NodeId, known_routes: &mut HashMap<NodeId, Vec<NodeInterfaceId>>) {
+        self.selected.clear();
+        self.selected.insert(
+            id,
+            (
+                None,
+                PathVectorRoute {
+                    destination: id,
+                    as_path: vec![],
+                    node_path: vec![],
+                    origin: RouteOrigin::Local,
+                },
+            ),
+        );
+        for (interface, neighbor) in self.neighbors.iter() {
+            for route in neighbor.routes.iter() {
+                let better = match self.selected.get(&route.destination) {
+                    Some((_, best)) => {
+                        (route.origin, route.as_path.len(), route.node_path.len())
+                            < (best.origin, best.as_path.len(), best.node_path.len())
+                    }
+                    None => true,
+                };
+                if better {
+                    self.selected
+                        .insert(route.destination, (Some(interface.clone()), route.clone()));
+                }
+            }
+        }
+
+        known_routes.clear();
+        for (destination, (interface, _)) in self.selected.iter() {
+            if let Some(interface) = interface {
+                known_routes.insert(*destination, vec![interface.clone()]);
+            }
+        }
+    }
+
+    fn advertisement(&self, router: &RouterContext, interface: &NodeInterfaceId) -> Payload {
+        let relationship = match self.neighbors.get(interface) {
+            Some(neighbor) => {
+                self.relationship(router.autonomous_system, neighbor.autonomous_system)
+            }
+            // Until we know who is there, only export what anyone may hear.
+            None => Some(Relationship::Provider),
+        };
+        let routes = self
+            .selected
+            .values()
+            .filter(|(learned_from, _)| learned_from.as_ref() != Some(interface))
+            .filter(|(_, route)| match relationship {
+                None | Some(Relationship::Customer) => true,
+                Some(_) => route.origin <= RouteOrigin::Customer,
+            })
+            .map(|(_, route)| {
+                let mut exported = route.clone();
+                exported.node_path.insert(0, router.id);
+                if let (Some(_), Some(autonomous_system)) = (relationship, router.autonomous_system)
+                {
+                    exported.as_path.insert(0, autonomous_system);
+                }
+                exported
+            })
+            .collect();
+        Payload::PathVector {
+            autonomous_system: router.autonomous_system,
+            routes,
+        }
+    }
+}
+
+impl RoutingProtocol for PathVector {
+    fn tick(&mut self, router: RouterContext, time: SimTime) -> Vec<(NodeInterfaceId, Payload)> {
+        let neighbors_count = self.neighbors.len();
+        let route_timeout = self.settings.route_timeout;
+        self.neighbors.retain(|interface, neighbor| {
+            time < neighbor.updated + route_timeout && router.interfaces.contains(interface)
+        });
+        if self.selected.is_empty() || self.neighbors.len() != neighbors_count {
+            self.select(router.id, router.known_routes);
+        }
+
+        if time < self.next_update {
+            return vec![];
+        }
+        self.next_update = time + self.settings.update_interval;
+        router
+            .interfaces
+            .iter()
+            .map(|interface| (interface.clone(), self.advertisement(&router, interface)))
+            .collect()
+    }
+
+    fn receive(
+        &mut self,
+        router: RouterContext,
+        interface: &NodeInterfaceId,
+        _sender: NodeId,
+        payload: &Payload,
+        time: SimTime,
+    ) -> Vec<(NodeInterfaceId, Payload)> {
+        let (autonomous_system, routes) = match payload {
+            Payload::PathVector {
+                autonomous_system,
+                routes,
+            } => (*autonomous_system, routes),
+            _ => return vec![],
+        };
+        let relationship = self.relationship(router.autonomous_system, autonomous_system);
+        let routes = routes
+            .iter()
+            .filter(|route| !route.node_path.contains(&router.id))
+            .filter(|route| {
+                router
+                    .autonomous_system
+                    .is_none_or(|ours| relationship.is_none() || !route.as_path.contains(&ours))
+            })
+            .map(|route| PathVectorRoute {
+                origin: match relationship {
+                    None => route.origin,
+                    Some(Relationship::Customer) => RouteOrigin::Customer,
+                    Some(Relationship::Peer) => RouteOrigin::Peer,
+                    Some(Relationship::Provider) => RouteOrigin::Provider,
+                },
+                ..route.clone()
+            })
+            .collect();
+        self.neighbors.insert(
+            interface.clone(),
+            Neighbor {
+                autonomous_system,
+                routes,
+                updated: time,
+            },
+        );
+        self.select(router.id, router.known_routes);
+        vec![]
+    }
+
+    fn routes(&self) -> Vec<Route> {
+        self.selected
+            .values()
+            .filter_map(|(interface, route)| {
+                interface.as_ref().map(|interface| Route {
+                    destination: route.destination,
+                    interfaces: vec![interface.clone()],
+                    metric: Some(route.node_path.len() as Cost),
+                    as_path: route.as_path.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::routing::testing::TestNetwork;
+
+    /// One router per autonomous system in `systems`.
+    fn network(
+        systems: &[AsNumber],
+        links: &[(NodeId, NodeId)],
+        settings: PathVectorSettings,
+    ) -> TestNetwork<PathVector> {
+        let mut network =
+            TestNetwork::new(systems.len(), links, || PathVector::new(settings.clone()));
+        for (router, autonomous_system) in network.routers.iter_mut().zip(systems) {
+            router.autonomous_system = Some(*autonomous_system);
+        }
+        network
+    }
+
+    fn rounds(network: &mut TestNetwork<PathVector>, count: SimTime) {
+        let update_interval = PathVectorSettings::default().update_interval;
+        for round in 0..count {
+            network.step(round * update_interval);
+        }
+    }
+
+    fn as_path(
+        network: &TestNetwork<PathVector>,
+        router: NodeId,
+        destination: NodeId,
+    ) -> Option<Vec<AsNumber>> {
+        network.routers[router]
+            .route(destination)
+            .map(|route| route.as_path)
+    }
+
+    #[test]
+    fn customers_do_not_carry_traffic_between_their_providers() {
+        // AS 3 buys transit from both AS 1 and AS 2, which aren't connected otherwise.
+        let mut settings = PathVectorSettings::default();
+        settings.add_provider(3, 1);
+        settings.add_provider(3, 2);
+        let mut network = network(&[1, 2, 3], &[(0, 2), (1, 2)], settings);
+        rounds(&mut network, 4);
+
+        assert_eq!(as_path(&network, 2, 0), Some(vec![1]));
+        assert_eq!(as_path(&network, 2, 1), Some(vec![2]));
+        assert_eq!(as_path(&network, 0, 2), Some(vec![3]));
+        assert_eq!(as_path(&network, 0, 1), None);
+        assert_eq!(as_path(&network, 1, 0), None);
+        assert!(!network.routers[0].known_routes.contains_key(&1));
+    }
+
+    #[test]
+    fn peer_routes_only_go_to_customers() {
+        // AS 2 peers with both AS 1 and AS 3.
+        let links = [(0, 1), (1, 2)];
+        let mut settings = PathVectorSettings::default();
+        settings.add_peers(1, 2);
+        settings.add_peers(2, 3);
+        let mut peers = network(&[1, 2, 3], &links, settings);
+        rounds(&mut peers, 4);
+        assert_eq!(as_path(&peers, 1, 2), Some(vec![3]));
+        assert_eq!(as_path(&peers, 0, 2), None);
+
+        // Once AS 1 is a customer of AS 2, it hears about AS 3...
+        let mut settings = PathVectorSettings::default();
+        settings.add_provider(1, 2);
+        settings.add_peers(2, 3);
+        let mut customer = network(&[1, 2, 3], &links, settings);
+        rounds(&mut customer, 4);
+        assert_eq!(as_path(&customer, 0, 2), Some(vec![2, 3]));
+        // ...and routes to customers go to peers as well.
+        assert_eq!(as_path(&customer, 2, 0), Some(vec![2, 1]));
+    }
+
+    #[test]
+    fn customer_routes_are_preferred_over_shorter_ones() {
+        // AS 4 is reachable through the peer AS 2 directly, or down the customer chain
+        // AS 3 > AS 5 > AS 4.
+        let mut settings = PathVectorSettings::default();
+        settings.add_peers(1, 2);
+        settings.add_provider(4, 2);
+        settings.add_provider(3, 1);
+        settings.add_provider(5, 3);
+        settings.add_provider(4, 5);
+        let mut network = network(
+            &[1, 2, 3, 5, 4],
+            &[(0, 1), (1, 4), (0, 2), (2, 3), (3, 4)],
+            settings,
+        );
+        rounds(&mut network, 5);
+
+        let route = network.routers[0].route(4).unwrap();
+        assert_eq!(route.as_path, [3, 5, 4]);
+        assert_eq!(route.interfaces, ["0-2"]);
+    }
+}
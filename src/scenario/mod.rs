@@ -0,0 +1,1105 @@
+use crate::network::error::NetworkError;
+use crate::network::ether::{Impairments, LinkAttributes};
+use crate::network::node::{AsNumber, NodeId, NodeKind};
+use crate::network::packet::{DEFAULT_TTL, PACKET_SIZE};
+use crate::network::queue::{DropPolicy, QueueSettings};
+use crate::network::routing::Routing;
+use crate::network::{Network, Point, ScheduledAction, SimTime};
+use serde::Deserialize;
+use serde_json;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{BufReader, Read};
+use uuid::Uuid;
+
+pub const SCENARIO_VERSION: u32 = 2;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub routing: Routing,
+    /// Output queues of the links that don't have their own settings.
+    #[serde(default)]
+    pub queue: QueueSettings,
+    pub nodes: Vec<NodeSpec>,
+    #[serde(default)]
+    pub links: Vec<LinkSpec>,
+    #[serde(default)]
+    pub autonomous_systems: Vec<AutonomousSystemSpec>,
+    #[serde(default)]
+    pub transmissions: Vec<TransmissionSpec>,
+    #[serde(default)]
+    pub events: Vec<EventSpec>,
+    #[serde(skip)]
+    legacy: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeSpec {
+    pub name: String,
+    pub kind: NodeKind,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub interfaces: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(from = "LinkSpecFields")]
+pub struct LinkSpec {
+    pub ends: [LinkEndSpec; 2],
+    pub attributes: LinkAttributes,
+}
+
+/// `LinkSpec` as written in scenarios, with the attributes next to the ends.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LinkSpecFields {
+    ends: [LinkEndSpec; 2],
+    #[serde(default)]
+    latency: Option<SimTime>,
+    #[serde(default)]
+    bandwidth: Option<f64>,
+    #[serde(default)]
+    loss: f64,
+    #[serde(default)]
+    corruption: f64,
+    #[serde(default)]
+    duplication: f64,
+    #[serde(default)]
+    jitter: SimTime,
+    #[serde(default)]
+    queue: Option<QueueSettings>,
+}
+
+impl From<LinkSpecFields> for LinkSpec {
+    fn from(fields: LinkSpecFields) -> Self {
+        LinkSpec {
+            ends: fields.ends,
+            attributes: LinkAttributes {
+                latency: fields.latency,
+                bandwidth: fields.bandwidth,
+                impairments: Impairments {
+                    loss: fields.loss,
+                    corruption: fields.corruption,
+                    duplication: fields.duplication,
+                    jitter: fields.jitter,
+                },
+                queue: fields.queue,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkEndSpec {
+    pub node: String,
+    #[serde(default)]
+    pub interface: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutonomousSystemSpec {
+    pub number: AsNumber,
+    pub nodes: Vec<String>,
+    /// Autonomous systems this one buys transit from; the reverse relationship is implied.
+    #[serde(default)]
+    pub providers: Vec<AsNumber>,
+    #[serde(default)]
+    pub peers: Vec<AsNumber>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransmissionSpec {
+    pub time: SimTime,
+    #[serde(default)]
+    pub uuid: Option<Uuid>,
+    pub source: String,
+    pub destination: String,
+    /// Packet size in bytes, `PACKET_SIZE` when absent.
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// `DEFAULT_TTL` when absent.
+    #[serde(default)]
+    pub ttl: Option<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(from = "EventSpecFields")]
+pub struct EventSpec {
+    pub time: SimTime,
+    pub action: EventActionSpec,
+}
+
+pub enum EventActionSpec {
+    /// Takes down the link between the two named nodes.
+    LinkDown {
+        link: [String; 2],
+    },
+    LinkUp {
+        link: [String; 2],
+    },
+    /// The node stops forwarding and everything sent to it is lost.
+    NodeFail {
+        node: String,
+    },
+    /// Brings a failed node back, with no routes.
+    NodeRestart {
+        node: String,
+    },
+    NodeRemove {
+        node: String,
+    },
+    /// Replaces the impairments of the link between the two named nodes.
+    Impair {
+        link: [String; 2],
+        impairments: Impairments,
+    },
+}
+
+/// `EventSpec` as written in scenarios: the time, the action and its arguments side by side.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+enum EventSpecFields {
+    LinkDown {
+        time: SimTime,
+        link: [String; 2],
+    },
+    LinkUp {
+        time: SimTime,
+        link: [String; 2],
+    },
+    NodeFail {
+        time: SimTime,
+        node: String,
+    },
+    NodeRestart {
+        time: SimTime,
+        node: String,
+    },
+    NodeRemove {
+        time: SimTime,
+        node: String,
+    },
+    Impair {
+        time: SimTime,
+        link: [String; 2],
+        #[serde(default)]
+        loss: f64,
+        #[serde(default)]
+        corruption: f64,
+        #[serde(default)]
+        duplication: f64,
+        #[serde(default)]
+        jitter: SimTime,
+    },
+}
+
+impl From<EventSpecFields> for EventSpec {
+    fn from(fields: EventSpecFields) -> Self {
+        let (time, action) = match fields {
+            EventSpecFields::LinkDown { time, link } => (time, EventActionSpec::LinkDown { link }),
+            EventSpecFields::LinkUp { time, link } => (time, EventActionSpec::LinkUp { link }),
+            EventSpecFields::NodeFail { time, node } => (time, EventActionSpec::NodeFail { node }),
+            EventSpecFields::NodeRestart { time, node } => {
+                (time, EventActionSpec::NodeRestart { node })
+            }
+            EventSpecFields::NodeRemove { time, node } => {
+                (time, EventActionSpec::NodeRemove { node })
+            }
+            EventSpecFields::Impair {
+                time,
+                link,
+                loss,
+                corruption,
+                duplication,
+                jitter,
+            } => (
+                time,
+                EventActionSpec::Impair {
+                    link,
+                    impairments: Impairments {
+                        loss,
+                        corruption,
+                        duplication,
+                        jitter,
+                    },
+                },
+            ),
+        };
+        EventSpec { time, action }
+    }
+}
+
+/// The original positional format: `[x, y, is_endpoint]` nodes, index pairs for cables
+/// and `[time, uuid, source, destination]` transmissions.
+#[derive(Deserialize)]
+struct LegacyScenario {
+    nodes: Vec<(i32, i32, bool)>,
+    cable_connections: Vec<(usize, usize)>,
+    transmissions: Vec<(u32, Uuid, usize, usize)>,
+    #[serde(default)]
+    seed: Option<u64>,
+}
+
+impl From<LegacyScenario> for Scenario {
+    fn from(legacy: LegacyScenario) -> Self {
+        Scenario {
+            seed: legacy.seed,
+            routing: Routing::Global,
+            queue: QueueSettings::default(),
+            nodes: legacy
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(index, (x, y, is_endpoint))| NodeSpec {
+                    name: index.to_string(),
+                    kind: if *is_endpoint {
+                        NodeKind::Endpoint
+                    } else {
+                        NodeKind::Router
+                    },
+                    x: *x,
+                    y: *y,
+                    interfaces: vec![],
+                })
+                .collect(),
+            links: legacy
+                .cable_connections
+                .iter()
+                .map(|(ind1, ind2)| LinkSpec {
+                    ends: [ind1, ind2].map(|index| LinkEndSpec {
+                        node: index.to_string(),
+                        interface: None,
+                    }),
+                    attributes: LinkAttributes::default(),
+                })
+                .collect(),
+            autonomous_systems: vec![],
+            transmissions: legacy
+                .transmissions
+                .iter()
+                .map(|(time, uuid, source, destination)| TransmissionSpec {
+                    time: *time as SimTime,
+                    uuid: Some(*uuid),
+                    source: source.to_string(),
+                    destination: destination.to_string(),
+                    size: None,
+                    ttl: None,
+                })
+                .collect(),
+            events: vec![],
+            legacy: true,
+        }
+    }
+}
+
+pub struct ValidationError {
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        LegacyScenario {
+            nodes: Vec::from([
+                (80, 80, true),
+                (150, 130, false),
+                (290, 50, false),
+                (80, 300, true),
+                (120, 510, true),
+                (200, 360, false),
+                (400, 520, true),
+                (320, 280, false),
+                (480, 120, false),
+                (650, 150, true),
+                (300, 370, false),
+                (560, 40, true),
+                (520, 320, false),
+                (630, 360, false),
+                (640, 440, false),
+                (570, 560, true),
+                (760, 490, true),
+            ]),
+            cable_connections: Vec::from([
+                (0, 1),
+                (1, 3),
+                (3, 5),
+                (4, 5),
+                (5, 10),
+                (10, 7),
+                (1, 7),
+                (1, 2),
+                (2, 8),
+                (8, 11),
+                (11, 9),
+                (6, 10),
+                (10, 12),
+                (7, 12),
+                (8, 12),
+                (12, 13),
+                (13, 14),
+                (14, 15),
+                (14, 16),
+                (9, 12),
+            ]),
+            transmissions: vec![],
+            seed: None,
+        }
+        .into()
+    }
+}
+
+fn queue_problems(settings: &QueueSettings) -> Vec<String> {
+    let mut problems = vec![];
+    if settings.capacity == 0 {
+        problems.push("queue capacity must be positive".to_string());
+    }
+    if settings.policy == DropPolicy::Red {
+        let red = &settings.red;
+        if red.min_threshold < 0.0 || red.min_threshold >= red.max_threshold {
+            problems.push(format!(
+                "RED thresholds {} and {} must satisfy 0 <= min < max",
+                red.min_threshold, red.max_threshold
+            ));
+        }
+        if red.max_probability <= 0.0 || red.max_probability > 1.0 {
+            problems.push(format!(
+                "RED max_probability {} is not a probability",
+                red.max_probability
+            ));
+        }
+        if red.weight <= 0.0 || red.weight > 1.0 {
+            problems.push(format!("RED weight {} must be in (0, 1]", red.weight));
+        }
+    }
+    problems
+}
+
+impl Scenario {
+    pub fn load<T: Read>(reader: BufReader<T>) -> Result<Self, String> {
+        let mut value: serde_json::Value =
+            serde_json::from_reader(reader).map_err(|err| format!("Serde error: {}", err))?;
+
+        match value.get("version").map(|version| version.as_u64()) {
+            None => serde_json::from_value::<LegacyScenario>(value)
+                .map(Scenario::from)
+                .map_err(|err| format!("Serde error (legacy format): {}", err)),
+            Some(Some(version)) if version == SCENARIO_VERSION as u64 => {
+                if let Some(fields) = value.as_object_mut() {
+                    fields.remove("version");
+                }
+                serde_json::from_value(value).map_err(|err| format!("Serde error: {}", err))
+            }
+            Some(_) => Err(format!(
+                "Unsupported scenario version {} (expected {})",
+                value["version"], SCENARIO_VERSION
+            )),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = vec![];
+        let mut error =
+            |location: String, message: String| errors.push(ValidationError { location, message });
+
+        let mut nodes = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let Some(first) = nodes.insert(node.name.as_str(), index) {
+                error(
+                    self.node_location(index),
+                    format!(
+                        "duplicate node name '{}' (first used by {})",
+                        node.name,
+                        self.node_location(first)
+                    ),
+                );
+                nodes.insert(node.name.as_str(), first);
+            }
+            let mut interfaces = HashSet::new();
+            for interface in node.interfaces.iter() {
+                if !interfaces.insert(interface) {
+                    error(
+                        self.node_location(index),
+                        format!("interface '{}' is declared twice", interface),
+                    );
+                }
+            }
+        }
+
+        let mut links = HashMap::new();
+        let mut used_interfaces = HashMap::new();
+        let mut adjacency: Vec<Vec<usize>> = vec![vec![]; self.nodes.len()];
+        for (index, link) in self.links.iter().enumerate() {
+            let [end1, end2] = &link.ends;
+            if end1.node == end2.node {
+                error(
+                    self.link_location(index, None),
+                    format!("node '{}' is linked to itself", end1.node),
+                );
+                continue;
+            }
+
+            let mut ends = vec![];
+            for (side, end) in link.ends.iter().enumerate() {
+                match nodes.get(end.node.as_str()) {
+                    Some(node) => ends.push(*node),
+                    None => error(
+                        self.link_location(index, Some(side)),
+                        format!("node '{}' does not exist", end.node),
+                    ),
+                }
+            }
+            let (node1, node2) = match ends[..] {
+                [node1, node2] => (node1, node2),
+                _ => continue,
+            };
+            if let Some(first) = links.insert((node1.min(node2), node1.max(node2)), index) {
+                error(
+                    self.link_location(index, None),
+                    format!(
+                        "nodes '{}' and '{}' are already linked by {}",
+                        end1.node,
+                        end2.node,
+                        self.link_location(first, None)
+                    ),
+                );
+                continue;
+            }
+            adjacency[node1].push(node2);
+            adjacency[node2].push(node1);
+
+            for (side, (node, end, peer)) in [(node1, end1, end2), (node2, end2, end1)]
+                .into_iter()
+                .enumerate()
+            {
+                let declared = &self.nodes[node].interfaces;
+                if let Some(interface) = &end.interface {
+                    if !declared.is_empty() && !declared.contains(interface) {
+                        error(
+                            self.link_location(index, Some(side)),
+                            format!(
+                                "interface '{}' is not declared on node '{}'",
+                                interface, end.node
+                            ),
+                        );
+                    }
+                }
+                let interface = end
+                    .interface
+                    .clone()
+                    .unwrap_or_else(|| format!("{}-{}", end.node, peer.node));
+                if let Some(first) = used_interfaces.insert((node, interface.clone()), index) {
+                    error(
+                        self.link_location(index, Some(side)),
+                        format!(
+                            "interface '{}' of node '{}' is already connected by {}",
+                            interface,
+                            end.node,
+                            self.link_location(first, None)
+                        ),
+                    );
+                }
+            }
+
+            let attributes = &link.attributes;
+            for (name, probability) in attributes.impairments.invalid_probabilities() {
+                error(
+                    self.link_location(index, None),
+                    format!("{} {} is not a probability", name, probability),
+                );
+            }
+            if let Some(bandwidth) = attributes.bandwidth {
+                if bandwidth <= 0.0 {
+                    error(
+                        self.link_location(index, None),
+                        format!("bandwidth {} must be positive", bandwidth),
+                    );
+                }
+            }
+            if let Some(queue) = &attributes.queue {
+                for message in queue_problems(queue) {
+                    error(self.link_location(index, None), message);
+                }
+            }
+        }
+        for message in queue_problems(&self.queue) {
+            error("queue".to_string(), message);
+        }
+
+        let mut component = vec![None; self.nodes.len()];
+        for start in 0..self.nodes.len() {
+            if component[start].is_some() || nodes[self.nodes[start].name.as_str()] != start {
+                continue;
+            }
+            component[start] = Some(start);
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                for next in adjacency[node].iter() {
+                    if component[*next].is_none() {
+                        component[*next] = Some(start);
+                        stack.push(*next);
+                    }
+                }
+            }
+            if start != 0 {
+                error(
+                    self.node_location(start),
+                    format!(
+                        "node '{}' is unreachable from node '{}'",
+                        self.nodes[start].name, self.nodes[0].name
+                    ),
+                );
+            }
+        }
+
+        let mut systems = HashMap::new();
+        let mut memberships = HashMap::new();
+        for (index, system) in self.autonomous_systems.iter().enumerate() {
+            if let Some(first) = systems.insert(system.number, index) {
+                error(
+                    format!("autonomous_systems[{}]", index),
+                    format!(
+                        "AS {} is already defined by autonomous_systems[{}]",
+                        system.number, first
+                    ),
+                );
+                systems.insert(system.number, first);
+            }
+            for (member, name) in system.nodes.iter().enumerate() {
+                let location = format!("autonomous_systems[{}].nodes[{}]", index, member);
+                match nodes.get(name.as_str()) {
+                    None => error(location, format!("node '{}' does not exist", name)),
+                    Some(node) => {
+                        if let Some(other) = memberships.insert(*node, system.number) {
+                            error(
+                                location,
+                                format!("node '{}' already belongs to AS {}", name, other),
+                            );
+                            memberships.insert(*node, other);
+                        }
+                    }
+                }
+            }
+        }
+        let mut relationships = HashSet::new();
+        for (index, system) in self.autonomous_systems.iter().enumerate() {
+            for (field, neighbors) in [("providers", &system.providers), ("peers", &system.peers)] {
+                for (position, neighbor) in neighbors.iter().enumerate() {
+                    let location = format!("autonomous_systems[{}].{}[{}]", index, field, position);
+                    if *neighbor == system.number {
+                        error(location, format!("AS {} is related to itself", neighbor));
+                    } else if !systems.contains_key(neighbor) {
+                        error(location, format!("AS {} is not defined", neighbor));
+                    } else if !relationships
+                        .insert((system.number.min(*neighbor), system.number.max(*neighbor)))
+                    {
+                        error(
+                            location,
+                            format!(
+                                "AS {} and AS {} already have a relationship",
+                                system.number, neighbor
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+        let path_vector = matches!(self.routing, Routing::PathVector(_));
+        if !self.autonomous_systems.is_empty() {
+            for (index, link) in self.links.iter().enumerate() {
+                let ends = link
+                    .ends
+                    .iter()
+                    .map(|end| {
+                        nodes
+                            .get(end.node.as_str())
+                            .map(|node| memberships.get(node))
+                    })
+                    .collect::<Option<Vec<_>>>();
+                match ends.as_deref() {
+                    Some(&[Some(&first), Some(&second)])
+                        if first != second
+                            && !relationships.contains(&(first.min(second), first.max(second))) =>
+                    {
+                        error(
+                            self.link_location(index, None),
+                            format!(
+                                "AS {} and AS {} are linked but have no relationship",
+                                first, second
+                            ),
+                        );
+                    }
+                    // Path-vector routing reports every such node below.
+                    Some([first, second])
+                        if first.is_some() != second.is_some() && !path_vector =>
+                    {
+                        let side = if first.is_none() { 0 } else { 1 };
+                        error(
+                            self.link_location(index, Some(side)),
+                            format!(
+                                "node '{}' belongs to no autonomous system",
+                                link.ends[side].node
+                            ),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        match &self.routing {
+            Routing::Global => {}
+            // Routers would take neighbors outside any autonomous system for peers.
+            Routing::PathVector(_) if !self.autonomous_systems.is_empty() => {
+                for (index, node) in self.nodes.iter().enumerate() {
+                    if nodes.get(node.name.as_str()) == Some(&index)
+                        && !memberships.contains_key(&index)
+                    {
+                        error(
+                            self.node_location(index),
+                            format!(
+                                "node '{}' belongs to no autonomous system, which path-vector routing needs",
+                                node.name
+                            ),
+                        );
+                    }
+                }
+            }
+            Routing::PathVector(_) => {}
+            Routing::DistanceVector(settings) => {
+                if settings.update_interval == 0 {
+                    error(
+                        "routing".to_string(),
+                        "update_interval must be positive".to_string(),
+                    );
+                }
+                if settings.infinity < 2 {
+                    error(
+                        "routing".to_string(),
+                        format!("infinity {} leaves no usable metrics", settings.infinity),
+                    );
+                }
+            }
+            Routing::LinkState(settings) => {
+                if settings.hello_interval == 0 {
+                    error(
+                        "routing".to_string(),
+                        "hello_interval must be positive".to_string(),
+                    );
+                }
+                if settings.dead_interval <= settings.hello_interval {
+                    error(
+                        "routing".to_string(),
+                        format!(
+                            "dead_interval {} must be longer than hello_interval {}",
+                            settings.dead_interval, settings.hello_interval
+                        ),
+                    );
+                }
+            }
+        }
+
+        let mut previous_time = 0;
+        for (index, transmission) in self.transmissions.iter().enumerate() {
+            if transmission.time < previous_time {
+                error(
+                    self.transmission_location(index, None),
+                    format!(
+                        "time {} is earlier than the previous transmission's {}",
+                        transmission.time, previous_time
+                    ),
+                );
+            }
+            previous_time = previous_time.max(transmission.time);
+            for (field, name) in [(0, &transmission.source), (1, &transmission.destination)] {
+                if !nodes.contains_key(name.as_str()) {
+                    error(
+                        self.transmission_location(index, Some(field)),
+                        format!("node '{}' does not exist", name),
+                    );
+                }
+            }
+            if transmission.size == Some(0) {
+                error(
+                    self.transmission_location(index, None),
+                    "size must be positive".to_string(),
+                );
+            }
+            if transmission.ttl == Some(0) {
+                error(
+                    self.transmission_location(index, None),
+                    "ttl must be positive".to_string(),
+                );
+            }
+        }
+
+        let mut events: Vec<(usize, &EventSpec)> = self.events.iter().enumerate().collect();
+        events.sort_by_key(|(_, event)| event.time);
+        let mut removed: HashMap<&str, usize> = HashMap::new();
+        for (index, event) in events {
+            let names: Vec<(String, &String)> = match &event.action {
+                EventActionSpec::LinkDown { link }
+                | EventActionSpec::LinkUp { link }
+                | EventActionSpec::Impair { link, .. } => link
+                    .iter()
+                    .enumerate()
+                    .map(|(side, name)| (format!("events[{}].link[{}]", index, side), name))
+                    .collect(),
+                EventActionSpec::NodeFail { node }
+                | EventActionSpec::NodeRestart { node }
+                | EventActionSpec::NodeRemove { node } => {
+                    vec![(format!("events[{}].node", index), node)]
+                }
+            };
+            let mut ends = vec![];
+            for (location, name) in names {
+                match nodes.get(name.as_str()) {
+                    Some(node) => ends.push(*node),
+                    None => error(location, format!("node '{}' does not exist", name)),
+                }
+                if let Some(removal) = removed.get(name.as_str()) {
+                    error(
+                        format!("events[{}]", index),
+                        format!("node '{}' is removed by events[{}]", name, removal),
+                    );
+                }
+            }
+            if let EventActionSpec::Impair { impairments, .. } = &event.action {
+                for (name, probability) in impairments.invalid_probabilities() {
+                    error(
+                        format!("events[{}]", index),
+                        format!("{} {} is not a probability", name, probability),
+                    );
+                }
+            }
+            match &event.action {
+                EventActionSpec::LinkDown { link }
+                | EventActionSpec::LinkUp { link }
+                | EventActionSpec::Impair { link, .. } => {
+                    if let [node1, node2] = ends[..] {
+                        if !links.contains_key(&(node1.min(node2), node1.max(node2))) {
+                            error(
+                                format!("events[{}]", index),
+                                format!("nodes '{}' and '{}' are not linked", link[0], link[1]),
+                            );
+                        }
+                    }
+                }
+                EventActionSpec::NodeRemove { node } => {
+                    removed.entry(node.as_str()).or_insert(index);
+                }
+                _ => {}
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn node_location(&self, index: usize) -> String {
+        format!("nodes[{}]", index)
+    }
+
+    fn link_location(&self, index: usize, side: Option<usize>) -> String {
+        match (self.legacy, side) {
+            (true, None) => format!("cable_connections[{}]", index),
+            (true, Some(side)) => format!("cable_connections[{}][{}]", index, side),
+            (false, None) => format!("links[{}]", index),
+            (false, Some(side)) => format!("links[{}].ends[{}]", index, side),
+        }
+    }
+
+    fn transmission_location(&self, index: usize, field: Option<usize>) -> String {
+        match (self.legacy, field) {
+            (_, None) => format!("transmissions[{}]", index),
+            (true, Some(field)) => format!("transmissions[{}][{}]", index, field + 2),
+            (false, Some(0)) => format!("transmissions[{}].source", index),
+            (false, Some(_)) => format!("transmissions[{}].destination", index),
+        }
+    }
+
+    /// Adds the scenario to the network, once it is known to be valid.
+    pub fn build(self, network: &mut Network) -> Result<Vec<NodeId>, NetworkError> {
+        self.validate().map_err(|errors| {
+            NetworkError::InvalidScenario(errors.iter().map(ValidationError::to_string).collect())
+        })?;
+        if let Some(seed) = self.seed {
+            network.set_seed(seed);
+        }
+
+        let mut declared_interfaces = HashSet::new();
+        let mut nodes: HashMap<&str, NodeId> = HashMap::new();
+        for node in self.nodes.iter() {
+            let position = Point::new(node.x, node.y);
+            let id = match node.kind {
+                NodeKind::Endpoint => network.add_endpoint_node(position),
+                NodeKind::Router => network.add_router_node(position),
+            };
+            for interface in node.interfaces.iter() {
+                network.add_router_interface(id, interface.clone())?;
+                declared_interfaces.insert((id, interface.clone()));
+            }
+            nodes.insert(node.name.as_str(), id);
+        }
+
+        let mut ethers = HashMap::new();
+        for link in self.links.iter() {
+            let [end1, end2] = &link.ends;
+            let mut side = |end: &LinkEndSpec, peer: &LinkEndSpec| {
+                let node = nodes[end.node.as_str()];
+                let interface = end
+                    .interface
+                    .clone()
+                    .unwrap_or_else(|| format!("{}-{}", end.node, peer.node));
+                if !declared_interfaces.contains(&(node, interface.clone())) {
+                    network.add_router_interface(node, interface.clone())?;
+                }
+                Ok::<_, NetworkError>((node, interface))
+            };
+            let sides = (side(end1, end2)?, side(end2, end1)?);
+            let (node1, node2) = (sides.0 .0, sides.1 .0);
+            let ether = network.connect_cable(sides, link.attributes.clone())?;
+            ethers.insert((node1.min(node2), node1.max(node2)), ether);
+        }
+
+        let mut routing = self.routing.clone();
+        for system in self.autonomous_systems.iter() {
+            for node in system.nodes.iter() {
+                network.set_autonomous_system(nodes[node.as_str()], Some(system.number))?;
+            }
+            if let Routing::PathVector(settings) = &mut routing {
+                for provider in system.providers.iter() {
+                    settings.add_provider(system.number, *provider);
+                }
+                for peer in system.peers.iter() {
+                    settings.add_peers(system.number, *peer);
+                }
+            }
+        }
+        network.set_queue_settings(self.queue.clone());
+        network.set_routing(routing)?;
+
+        for transmission in self.transmissions.iter() {
+            let uuid = transmission.uuid.unwrap_or_else(|| network.generate_uuid());
+            network.schedule_send(
+                transmission.time,
+                uuid,
+                nodes[transmission.source.as_str()],
+                nodes[transmission.destination.as_str()],
+                transmission.size.unwrap_or(PACKET_SIZE),
+                transmission.ttl.unwrap_or(DEFAULT_TTL),
+            )?;
+        }
+
+        for event in self.events.iter() {
+            let ether = |link: &[String; 2]| {
+                let (node1, node2) = (nodes[link[0].as_str()], nodes[link[1].as_str()]);
+                ethers[&(node1.min(node2), node1.max(node2))]
+            };
+            let action = match &event.action {
+                EventActionSpec::LinkDown { link } => ScheduledAction::LinkDown(ether(link)),
+                EventActionSpec::LinkUp { link } => ScheduledAction::LinkUp(ether(link)),
+                EventActionSpec::NodeFail { node } => {
+                    ScheduledAction::NodeFail(nodes[node.as_str()])
+                }
+                EventActionSpec::NodeRestart { node } => {
+                    ScheduledAction::NodeRestart(nodes[node.as_str()])
+                }
+                EventActionSpec::NodeRemove { node } => {
+                    ScheduledAction::NodeRemove(nodes[node.as_str()])
+                }
+                EventActionSpec::Impair { link, impairments } => {
+                    ScheduledAction::Impair(ether(link), impairments.clone())
+                }
+            };
+            network.schedule(event.time, action)?;
+        }
+
+        Ok(self
+            .nodes
+            .iter()
+            .map(|node| nodes[node.name.as_str()])
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(json: &str) -> Result<Scenario, String> {
+        Scenario::load(BufReader::new(json.as_bytes()))
+    }
+
+    fn locations(scenario: &Scenario) -> Vec<String> {
+        match scenario.validate() {
+            Ok(()) => vec![],
+            Err(errors) => errors.into_iter().map(|error| error.location).collect(),
+        }
+    }
+
+    const NODES: &str = r#"
+        { "name": "a", "kind": "endpoint", "x": 0, "y": 0 },
+        { "name": "r", "kind": "router", "x": 100, "y": 0 },
+        { "name": "b", "kind": "endpoint", "x": 200, "y": 0 }"#;
+
+    #[test]
+    fn valid_scenario() {
+        let scenario = load(&format!(
+            r#"{{
+                "version": 2,
+                "nodes": [{}],
+                "links": [
+                    {{ "ends": [{{ "node": "a" }}, {{ "node": "r" }}], "loss": 0.1, "queue": {{ "capacity": 4 }} }},
+                    {{ "ends": [{{ "node": "r" }}, {{ "node": "b" }}] }}
+                ],
+                "transmissions": [{{ "time": 10, "source": "a", "destination": "b" }}],
+                "events": [{{ "time": 20, "action": "impair", "link": ["r", "b"], "jitter": 5 }}]
+            }}"#,
+            NODES
+        ))
+        .unwrap();
+        assert!(locations(&scenario).is_empty());
+        let link = &scenario.links[0].attributes;
+        assert_eq!(link.impairments.loss, 0.1);
+        assert_eq!(link.queue.as_ref().map(|queue| queue.capacity), Some(4));
+    }
+
+    #[test]
+    fn error_locations() {
+        let scenario = load(&format!(
+            r#"{{
+                "version": 2,
+                "nodes": [{}, {{ "name": "a", "kind": "router", "x": 0, "y": 100 }}],
+                "links": [
+                    {{ "ends": [{{ "node": "a" }}, {{ "node": "x" }}] }},
+                    {{ "ends": [{{ "node": "r" }}, {{ "node": "r" }}] }},
+                    {{ "ends": [{{ "node": "a" }}, {{ "node": "r" }}], "duplication": 2 }}
+                ],
+                "transmissions": [
+                    {{ "time": 10, "source": "a", "destination": "y" }},
+                    {{ "time": 5, "source": "a", "destination": "r", "ttl": 0 }}
+                ],
+                "events": [{{ "time": 20, "action": "link_down", "link": ["a", "b"] }}]
+            }}"#,
+            NODES
+        ))
+        .unwrap();
+        assert_eq!(
+            locations(&scenario),
+            [
+                "nodes[3]",
+                "links[0].ends[1]",
+                "links[1]",
+                "links[2]",
+                "nodes[2]",
+                "transmissions[0].destination",
+                "transmissions[1]",
+                "transmissions[1]",
+                "events[0]",
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_locations() {
+        let scenario = load(
+            r#"{
+                "nodes": [[0, 0, true], [100, 0, false]],
+                "cable_connections": [[0, 1], [1, 0]],
+                "transmissions": [[10, "00000000-0000-0000-0000-000000000001", 0, 2]]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            locations(&scenario),
+            ["cable_connections[1]", "transmissions[0][3]"]
+        );
+    }
+
+    #[test]
+    fn path_vector_needs_every_node_in_a_system() {
+        let scenario = |routing: &str| {
+            load(&format!(
+                r#"{{
+                    "version": 2,
+                    "routing": {{ "protocol": "{}" }},
+                    "nodes": [{}, {{ "name": "c", "kind": "endpoint", "x": 100, "y": 100 }}],
+                    "links": [
+                        {{ "ends": [{{ "node": "a" }}, {{ "node": "r" }}] }},
+                        {{ "ends": [{{ "node": "r" }}, {{ "node": "b" }}] }},
+                        {{ "ends": [{{ "node": "r" }}, {{ "node": "c" }}] }}
+                    ],
+                    "autonomous_systems": [{{ "number": 1, "nodes": ["a", "r", "b"] }}]
+                }}"#,
+                routing, NODES
+            ))
+            .unwrap()
+        };
+        assert_eq!(locations(&scenario("path_vector")), ["nodes[3]"]);
+        assert_eq!(locations(&scenario("global")), ["links[2].ends[1]"]);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        for link in [
+            r#"{ "ends": [{ "node": "a" }, { "node": "r" }], "capacity": 4 }"#,
+            r#"{ "ends": [{ "node": "a" }, { "node": "r" }], "queue": { "weigth": 0.5 } }"#,
+            r#"{ "ends": [{ "node": "a", "port": "eth0" }, { "node": "r" }] }"#,
+        ] {
+            let json = format!(
+                r#"{{ "version": 2, "nodes": [{}], "links": [{}] }}"#,
+                NODES, link
+            );
+            assert!(load(&json).is_err(), "{}", link);
+        }
+        let event = r#"{ "time": 5, "action": "node_fail", "node": "r", "loss": 1 }"#;
+        let json = format!(
+            r#"{{ "version": 2, "nodes": [{}], "events": [{}] }}"#,
+            NODES, event
+        );
+        assert!(load(&json).is_err());
+        let routing = r#"{ "protocol": "link_state", "hello": 10 }"#;
+        let json = format!(
+            r#"{{ "version": 2, "nodes": [{}], "routing": {} }}"#,
+            NODES, routing
+        );
+        assert!(load(&json).is_err());
+    }
+
+    #[test]
+    fn build_rejects_invalid_scenario() {
+        let scenario = load(&format!(
+            r#"{{
+                "version": 2,
+                "nodes": [{}],
+                "links": [{{ "ends": [{{ "node": "a" }}, {{ "node": "nowhere" }}] }}]
+            }}"#,
+            NODES
+        ))
+        .unwrap();
+        let mut network = Network::new();
+        assert!(matches!(
+            scenario.build(&mut network),
+            Err(NetworkError::InvalidScenario(errors)) if errors.len() == 3
+        ));
+        assert_eq!(network.nodes().count(), 0);
+    }
+}